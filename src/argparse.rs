@@ -1,10 +1,114 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(version, about)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Build the site from the input folder into the output folder
+    Build(SiteArgs),
+    /// Build the site, serve the output folder on localhost and rebuild whenever the input folder changes
+    Serve {
+        #[command(flatten)]
+        site: SiteArgs,
+        #[arg(short = 'p', long = "port", default_value_t = 8080)]
+        port: u16,
+    },
+    /// Scaffold a new post in the posts folder
+    New {
+        #[arg(short = 'i', long = "input")]
+        input_dir: PathBuf,
+        /// The title of the new post. Asked for when left out
+        title: Option<String>,
+        /// A category for the new post. Can be given more than once
+        #[arg(short = 'c', long = "category")]
+        categories: Vec<String>,
+        /// A short summary of the post
+        #[arg(short = 's', long = "summary")]
+        summary: Option<String>,
+    },
+    /// Validate the input folder without writing anything
+    Check {
+        #[arg(short = 'i', long = "input")]
+        input_dir: PathBuf,
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// The most threads to render posts with. Defaults to one per CPU core
+        #[arg(short = 'j', long = "jobs", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        jobs: Option<usize>,
+    },
+    /// Remove the generated site from the output folder
+    Clean {
+        #[arg(short = 'o', long = "output")]
+        output_dir: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct SiteArgs {
     #[arg(short = 'i', long = "input")]
-    pub input_dir: std::path::PathBuf,
+    pub input_dir: PathBuf,
     #[arg(short = 'o', long = "output")]
-    pub output_dir: std::path::PathBuf,
+    pub output_dir: PathBuf,
+    /// The most threads to render posts with. Defaults to one per CPU core
+    #[arg(short = 'j', long = "jobs", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub jobs: Option<usize>,
+    #[command(flatten)]
+    pub publish: PublishArgs,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+}
+
+/// Which of the posts that aren't live yet, or any more, to build anyway.
+#[derive(Args, Clone, Copy, Default)]
+pub struct PublishArgs {
+    /// Include posts marked as drafts
+    #[arg(long = "drafts")]
+    pub drafts: bool,
+    /// Include posts dated in the future
+    #[arg(long = "future")]
+    pub future: bool,
+    /// Include posts past their expiry date
+    #[arg(long = "expired")]
+    pub expired: bool,
+}
+
+/// Changes to the site config for one run, on top of what `config.yaml` says.
+#[derive(Args, Clone, Default)]
+pub struct ConfigArgs {
+    /// Merge config.<ENV>.yaml over the config, like `--env staging`
+    #[arg(long = "env", value_parser = parse_env)]
+    pub env: Option<String>,
+    /// Set a config key, like `--set url=https://staging.example.com`. Can be given more than once
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+}
+
+fn parse_env(env: &str) -> Result<String, String> {
+    if !env.is_empty()
+        && env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(env.to_string())
+    } else {
+        Err(String::from(
+            "should only use letters, numbers, - and _, like staging",
+        ))
+    }
+}
+
+fn parse_override(setting: &str) -> Result<(String, String), String> {
+    match setting.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(String::from("should look like key=value")),
+    }
 }
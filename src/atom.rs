@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use atom_syndication::{
+    CategoryBuilder, ContentBuilder, EntryBuilder, FeedBuilder, GeneratorBuilder, LinkBuilder,
+    PersonBuilder, Text,
+};
+use chrono::Utc;
+
+use crate::config::SiteConfig;
+use crate::error::BuildError;
+use crate::feed::{feed_content, feed_posts};
+use crate::files::write_to_file;
+use crate::posts::Post;
+
+/// build_atom_feed
+///
+/// Writes an Atom 1.0 feed of every post to atom.xml, next to the RSS feed.
+/// Every timestamp comes from the post dates, so building the same posts twice gives the same feed.
+pub fn build_atom_feed(
+    output_dir: &Path,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
+    let author = PersonBuilder::default().name(config.author.clone()).build();
+
+    let posts = feed_posts(posts, config);
+    let mut entries = Vec::new();
+    for post in &posts {
+        let categories: Vec<_> = post
+            .metadata
+            .categories
+            .iter()
+            .map(|category| CategoryBuilder::default().term(category.clone()).build())
+            .collect();
+
+        let mut entry = EntryBuilder::default();
+        entry
+            .title(Text::plain(post.metadata.title.clone()))
+            .id(post.public_link.clone())
+            .updated(post.metadata.published_at())
+            .published(Some(post.metadata.published_at().fixed_offset()))
+            .authors(vec![author.clone()])
+            .categories(categories)
+            .links(vec![LinkBuilder::default()
+                .href(post.public_link.clone())
+                .rel("alternate")
+                .build()])
+            .content(feed_content(post, config).map(|content| {
+                ContentBuilder::default()
+                    .value(Some(content))
+                    .content_type(Some(String::from("html")))
+                    .build()
+            }));
+        if !post.metadata.summary.is_empty() {
+            entry.summary(Some(Text::plain(post.metadata.summary.clone())));
+        }
+        entries.push(entry.build());
+    }
+
+    // The feed was last updated when its newest post was
+    let updated = posts
+        .first()
+        .map(|post| post.metadata.published_at())
+        .unwrap_or_else(Utc::now);
+
+    let feed = FeedBuilder::default()
+        .title(Text::plain(config.title.clone()))
+        .subtitle(Some(Text::plain(config.description.clone())))
+        .id(config.site_url())
+        .updated(updated)
+        .authors(vec![author])
+        .links(vec![
+            LinkBuilder::default()
+                .href(config.public_url("atom.xml"))
+                .rel("self")
+                .build(),
+            LinkBuilder::default()
+                .href(config.site_url())
+                .rel("alternate")
+                .build(),
+        ])
+        .generator(Some(
+            GeneratorBuilder::default()
+                .value(String::from("blog-builder"))
+                .version(Some(String::from(env!("CARGO_PKG_VERSION"))))
+                .build(),
+        ))
+        .entries(entries)
+        .build();
+
+    write_to_file(output_dir, "atom.xml", &feed.to_string())
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::SiteConfig, error::BuildError, files::write_to_file};
+
+/// The cache lives in the output directory, next to the pages it describes.
+pub const CACHE_FILE_NAME: &str = ".blog-builder-cache";
+
+/// Starts each line of the cache that holds a rendered post, rather than the hash of an input.
+const RENDERED_PREFIX: &str = "rendered ";
+
+/// RenderedPost
+///
+/// What a post's markdown rendered to, kept so an unchanged post isn't rendered again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedPost {
+    pub body: String,
+    pub summary: String,
+}
+
+/// BuildCache
+///
+/// Remembers a hash of every input from the previous build so that unchanged posts aren't
+/// rendered again and unchanged images aren't copied again. The rendered body and summary of each
+/// post are kept too, as the feeds and listings need them even when its page is reused.
+/// Everything is also keyed on a site wide hash of the config and templates: when that changes
+/// every page is treated as changed, as any of them could look different.
+#[derive(Default)]
+pub struct BuildCache {
+    path: Option<PathBuf>,
+    site_hash: u64,
+    previous: HashMap<String, u64>,
+    current: HashMap<String, u64>,
+    previous_rendered: HashMap<String, RenderedPost>,
+    rendered: HashMap<String, RenderedPost>,
+}
+
+impl BuildCache {
+    /// load
+    ///
+    /// Reads the cache left in the output directory by the previous build.
+    /// A missing or unreadable cache, or one made for a different config or templates, is simply empty.
+    pub fn load(output_dir: &Path, site_hash: u64) -> BuildCache {
+        let path = output_dir.join(CACHE_FILE_NAME);
+        let mut previous = HashMap::new();
+        let mut previous_rendered = HashMap::new();
+
+        if let Ok(file_contents) = fs::read_to_string(&path) {
+            let mut lines = file_contents.lines();
+            let stored_site_hash = lines
+                .next()
+                .and_then(|line| line.strip_prefix("site "))
+                .and_then(|hash| u64::from_str_radix(hash, 16).ok());
+
+            if stored_site_hash == Some(site_hash) {
+                for line in lines {
+                    if let Some(json) = line.strip_prefix(RENDERED_PREFIX) {
+                        if let Ok((key, post)) = serde_json::from_str(json) {
+                            previous_rendered.insert(key, post);
+                        }
+                    } else if let Some((hash, key)) = line.split_once(' ') {
+                        if let Ok(hash) = u64::from_str_radix(hash, 16) {
+                            previous.insert(key.to_string(), hash);
+                        }
+                    }
+                }
+            }
+        }
+
+        BuildCache {
+            path: Some(path),
+            site_hash,
+            previous,
+            current: HashMap::new(),
+            previous_rendered,
+            rendered: HashMap::new(),
+        }
+    }
+
+    /// is_unchanged
+    ///
+    /// Returns whether the hash of an input matches the one from the previous build.
+    pub fn is_unchanged(&self, key: &str, hash: u64) -> bool {
+        self.previous.get(key) == Some(&hash)
+    }
+
+    /// record
+    ///
+    /// Remembers the hash of an input for this build, to be saved for the next one.
+    pub fn record(&mut self, key: &str, hash: u64) {
+        self.current.insert(key.to_string(), hash);
+    }
+
+    /// rendered
+    ///
+    /// What a post rendered to in the previous build, when its source still has the same hash.
+    pub fn rendered(&self, key: &str, hash: u64) -> Option<&RenderedPost> {
+        if self.is_unchanged(key, hash) {
+            self.previous_rendered.get(key)
+        } else {
+            None
+        }
+    }
+
+    /// record_rendered
+    ///
+    /// Remembers what a post rendered to in this build, to be saved for the next one.
+    pub fn record_rendered(&mut self, key: &str, post: RenderedPost) {
+        self.rendered.insert(key.to_string(), post);
+    }
+
+    /// output_key
+    ///
+    /// The key for a file written into the output directory, relative to it so the cache
+    /// doesn't depend on how the output path was typed.
+    pub fn output_key(&self, path: &Path) -> String {
+        let output_dir = self.path.as_deref().and_then(Path::parent);
+        let relative = match output_dir {
+            Some(output_dir) => path.strip_prefix(output_dir).unwrap_or(path),
+            None => path,
+        };
+        relative.to_string_lossy().into_owned()
+    }
+
+    /// save
+    ///
+    /// Writes the hashes recorded during this build for the next one to compare against.
+    pub fn save(&self) -> Result<(), BuildError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut entries: Vec<(&String, &u64)> = self.current.iter().collect();
+        entries.sort();
+
+        let mut contents = format!("site {:016x}\n", self.site_hash);
+        for (key, hash) in entries {
+            contents.push_str(&format!("{:016x} {}\n", hash, key));
+        }
+        let mut rendered: Vec<(&String, &RenderedPost)> = self.rendered.iter().collect();
+        rendered.sort_by_key(|(key, _)| *key);
+        for entry in rendered {
+            // Each post is written as JSON, which keeps it on a single line whatever it contains
+            let json = serde_json::to_string(&entry)
+                .map_err(|e| BuildError::Message(format!("Couldn't save the cache: {}", e)))?;
+            contents.push_str(RENDERED_PREFIX);
+            contents.push_str(&json);
+            contents.push('\n');
+        }
+
+        let directory = path.parent().unwrap_or(Path::new("."));
+        write_to_file(directory, CACHE_FILE_NAME, &contents)
+    }
+}
+
+/// site_hash
+///
+/// Hashes everything that changes how every page looks: the config, the templates, the layouts
+/// and the version of this tool. Missing files hash as empty, the build reports them itself.
+/// The config is hashed as it was read, so switching environment or overriding a key rebuilds every page too.
+pub fn site_hash(input_dir: &Path, config: &SiteConfig) -> u64 {
+    let mut contents = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+    contents.extend(format!("{:?}", config).as_bytes());
+    for file_name in ["header.md", "footer.md", "index.html"] {
+        contents.push(0);
+        contents.extend(fs::read(input_dir.join(file_name)).unwrap_or_default());
+    }
+
+    // Layouts change how pages look just as much as the templates do
+    let mut layout_paths: Vec<_> = fs::read_dir(input_dir.join("layouts"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    layout_paths.sort();
+    for path in layout_paths {
+        contents.push(0);
+        contents.extend(path.to_string_lossy().as_bytes());
+        contents.push(0);
+        contents.extend(fs::read(&path).unwrap_or_default());
+    }
+    hash_bytes(&contents)
+}
+
+/// hash_bytes
+///
+/// 64-bit FNV-1a. Not cryptographic, but stable between runs and versions of Rust,
+/// which the standard library's hasher doesn't promise.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
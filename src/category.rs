@@ -1,4 +1,10 @@
-use crate::posts::Post;
+use std::cmp::Reverse;
+
+use crate::{
+    config::Taxonomy,
+    posts::{create_post_preview_html, Post},
+    slug::slugify,
+};
 
 #[derive(Debug)]
 pub struct Category {
@@ -6,31 +12,74 @@ pub struct Category {
     pub path: String,
 }
 
+/// Each category, or term of another taxonomy, paired with the posts that belong to it, in the order they were found.
+pub type CategoryPosts = Vec<(Category, Vec<Post>)>;
+
+/// group_by_term
+///
+/// Gathers the posts of each term of a taxonomy, with the terms in the order their first post was read.
+pub fn group_by_term(posts: &[Post], taxonomy: &Taxonomy) -> CategoryPosts {
+    let mut terms: CategoryPosts = Vec::new();
+    for post in posts {
+        for term in post.metadata.terms(&taxonomy.name) {
+            match terms
+                .iter_mut()
+                .find(|(existing, _)| existing.name == *term)
+            {
+                Some((_, term_posts)) => term_posts.push(post.clone()),
+                None => terms.push((
+                    Category {
+                        name: term.clone(),
+                        path: taxonomy.term_path(term),
+                    },
+                    vec![post.clone()],
+                )),
+            }
+        }
+    }
+    terms
+}
+
+/// get_category_path
+///
+/// The page of a category at the top of the site, named after its slug, like `c-plus-plus.html` for C++.
 pub fn get_category_path(category: &str) -> String {
-    let prefix = category
-        .chars()
-        .filter(|&c| !c.is_whitespace())
-        .collect::<String>()
-        .to_lowercase();
+    format!("{}.html", slugify(category))
+}
 
-    format!("{}.html", prefix)
+impl Category {
+    /// feed_path
+    ///
+    /// The RSS feed for a category sits next to its page, `blog-writing.html` gets `blog-writing.xml`.
+    pub fn feed_path(&self) -> String {
+        let slug = self.path.strip_suffix(".html").unwrap_or(&self.path);
+        format!("{}.xml", slug)
+    }
 }
 
-pub fn create_category_list_html(category: &Category, posts: &Vec<Post>) -> String {
+pub fn create_category_list_html(
+    category: &Category,
+    posts: &[Post],
+    with_previews: bool,
+) -> String {
     let mut category_list_html = String::from("<div id=\"category-list\">\n<h2>");
     category_list_html.push_str(&category.name);
     category_list_html.push_str("</h2>\n<ul>\n");
 
     // Sort posts so that the most recent is first
-    let mut sorted_posts = posts.clone();
-    sorted_posts.sort_by(|a, b| b.metadata.date.cmp(&a.metadata.date));
+    let mut sorted_posts = posts.to_vec();
+    sorted_posts.sort_by_key(|post| Reverse(post.metadata.date));
 
     for post in sorted_posts {
+        if with_previews {
+            category_list_html.push_str(&format!("<li>{}</li>\n", create_post_preview_html(&post)));
+            continue;
+        }
         let post_html = format!(
             "<li><a href=\"{}\">{} - [{}]</a></li>\n",
             post.path,
             post.metadata.title,
-            post.metadata.date.format("%-d %B %Y").to_string()
+            post.metadata.date.format("%-d %B %Y")
         );
         category_list_html.push_str(&post_html);
     }
@@ -1,35 +1,918 @@
-use std::{io::Error, path::Path};
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
 
-use yaml_rust::YamlLoader;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use yaml_rust::{Yaml, YamlLoader};
 
-use crate::files::read_file;
+use crate::{
+    argparse::ConfigArgs,
+    category::get_category_path,
+    error::BuildError,
+    files::read_file,
+    permalink::{validate_permalink, DEFAULT_PERMALINK},
+};
 
+#[derive(Debug)]
 pub struct SiteConfig {
     pub title: String,
+    /// Where the site is published, as written in the config. Use `site_url` and `public_url` to build links.
     pub url: String,
+    /// The path the site is served from, like `/notes`, or empty when it is served from the root of its domain.
+    /// Taken from `url` unless the config sets it.
+    pub base_path: String,
     pub description: String,
+    /// Who the feeds credit as the author. Falls back to the site title.
+    pub author: String,
+    /// Which feed formats to write. Every format is written unless `feeds` lists some.
+    pub feeds: Vec<FeedFormat>,
+    /// Whether feed items carry the whole post or only its summary.
+    pub feed_content: FeedContent,
+    /// The most posts a feed will hold, newest first. Every post is included when this isn't set.
+    pub feed_items: Option<usize>,
+    /// How many words of a post make its excerpt, when it has neither a summary nor a `<!-- more -->` marker.
+    pub excerpt_words: usize,
+    /// Which listing pages show a preview of each post rather than just its title. None do unless listed.
+    pub previews: Vec<Listing>,
+    /// How links between the pages of the site are written.
+    pub links: LinkMode,
+    /// The pattern every post's URL follows, such as `/:year/:month/:slug/`.
+    pub permalink: String,
+    /// Which redirect rule files to write for the host, alongside the redirect pages for aliases.
+    pub redirect_rules: Vec<RedirectRules>,
+    /// Folders of posts besides `posts`, each listed on its own page.
+    pub sections: Vec<Section>,
+    /// Markdown files in the input directory built into pages of their own, like `about.md`.
+    pub pages: Vec<String>,
+    /// Ways of grouping posts besides their categories, like tags or series.
+    pub taxonomies: Vec<Taxonomy>,
+}
+
+/// Section
+///
+/// A folder of posts besides `posts`, such as `notes`, whose posts are written to the same folder in the output
+/// and listed on a page of their own rather than with the other posts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Section {
+    /// The folder in the input directory, which also names the folder and listing page in the output.
+    pub folder: String,
+    /// The heading of the listing page. Defaults to the folder.
+    #[serde(default)]
+    pub title: String,
+    /// Whether the section's posts go in the site's feeds.
+    #[serde(default = "default_true")]
+    pub feeds: bool,
+    /// The layout its posts are rendered with instead of `post.html`.
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+impl Section {
+    /// listing_path
+    ///
+    /// The page listing the section's posts, like `notes.html`.
+    pub fn listing_path(&self) -> String {
+        format!("{}.html", self.folder)
+    }
+}
+
+/// The taxonomy every site has, set by the `categories` of each post.
+pub const CATEGORIES: &str = "categories";
+
+/// Taxonomy
+///
+/// A way of grouping posts by the terms listed under a front matter key, like `tags: [rust, cli]`.
+/// Every taxonomy gets an index page listing its terms and a page for each term.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Taxonomy {
+    /// The front matter key holding a post's terms, which also names the taxonomy's index page and folder.
+    pub name: String,
+    /// The heading of the index page. Defaults to the name.
+    #[serde(default)]
+    pub title: String,
+    /// Whether each term gets its own RSS feed, when RSS is enabled.
+    #[serde(default)]
+    pub feeds: bool,
+}
+
+impl Taxonomy {
+    /// categories
+    ///
+    /// The built in taxonomy, whose pages sit at the top of the site as they always have.
+    pub fn categories() -> Taxonomy {
+        Taxonomy {
+            name: String::from(CATEGORIES),
+            title: String::from("Categories"),
+            feeds: true,
+        }
+    }
+
+    /// index_path
+    ///
+    /// The page listing every term, like `tags.html`.
+    pub fn index_path(&self) -> String {
+        format!("{}.html", self.name)
+    }
+
+    /// term_path
+    ///
+    /// The page of one term, like `tags/rust.html`. Category pages sit at the top of the site instead, like `rust.html`.
+    pub fn term_path(&self, term: &str) -> String {
+        if self.name == CATEGORIES {
+            get_category_path(term)
+        } else {
+            format!("{}/{}", self.name, get_category_path(term))
+        }
+    }
+}
+
+/// Folders and page names a section or taxonomy can't use, as the site already has something by that name.
+const RESERVED_NAMES: [&str; 7] = [
+    "posts",
+    "images",
+    "style",
+    "layouts",
+    "index",
+    "all",
+    "categories",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    /// RSS 2.0, written to feed.xml
+    Rss,
+    /// Atom 1.0, written to atom.xml
+    Atom,
+    /// JSON Feed 1.1, written to feed.json
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedContent {
+    /// Each item holds the full post
+    #[default]
+    Full,
+    /// Each item holds only the summary, readers follow the link for the rest
+    Summary,
+}
+
+/// How links to other pages of the site are written in the generated HTML.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkMode {
+    /// `../images/blog.jpg`, so the site works from any folder, even opened straight from disk
+    #[default]
+    Relative,
+    /// `/images/blog.jpg`
+    Root,
+    /// `https://example.com/images/blog.jpg`, using `url`
+    Absolute,
+}
+
+/// Redirect rule files for hosts that can redirect without a page.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectRules {
+    /// `_redirects`, for Netlify and Cloudflare Pages
+    Netlify,
+    /// `redirects.conf`, to `include` in an nginx server block
+    Nginx,
+}
+
+/// The pages that list posts.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Listing {
+    /// The recent posts on index.html
+    Index,
+    /// all.html
+    AllPosts,
+    /// The page of each category
+    Categories,
+}
+
+impl RedirectRules {
+    /// file_name
+    ///
+    /// Where the rules are written, at the top of the site.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            RedirectRules::Netlify => "_redirects",
+            RedirectRules::Nginx => "redirects.conf",
+        }
+    }
+}
+
+impl FeedFormat {
+    pub const ALL: [FeedFormat; 3] = [FeedFormat::Rss, FeedFormat::Atom, FeedFormat::Json];
+
+    /// file_name
+    ///
+    /// Where the feed of the whole site is written in this format.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "feed.xml",
+            FeedFormat::Atom => "atom.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
 }
 
 impl SiteConfig {
-    pub fn read_site_config(input_dir: &Path) -> Result<SiteConfig, Error> {
-        let config_path = Path::new(input_dir).join("config.yaml");
-        match read_file(&config_path) {
-            Ok(file_contents) => {
-                let yaml = YamlLoader::load_from_str(&file_contents).unwrap();
-                let yaml = &yaml[0];
-                let title = yaml["title"].as_str().unwrap();
-                let url = yaml["url"].as_str().unwrap();
-                let description = yaml["description"].as_str().unwrap();
-                Ok(SiteConfig {
-                    title: title.to_string(),
-                    url: url.to_string(),
-                    description: description.to_string(),
+    /// site_url
+    ///
+    /// The address of the top of the site, such as `https://example.com/notes`, without a trailing slash.
+    pub fn site_url(&self) -> String {
+        format!("{}{}", url_origin(&self.url), self.base_path)
+    }
+
+    /// section
+    ///
+    /// The section kept in the given folder of the input directory, if any.
+    pub fn section(&self, folder: &str) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.folder == folder)
+    }
+
+    /// public_url
+    ///
+    /// The full address of a path within the site, such as `posts.html` or `2023/08/my-post/`.
+    pub fn public_url(&self, path: &str) -> String {
+        format!("{}/{}", self.site_url(), path)
+    }
+
+    /// read_site_config
+    ///
+    /// Reads `config.yaml`, or `config.toml` for those who prefer it, from the input directory.
+    /// The `config.<env>.yaml` of the chosen environment is merged over it, then every `--set` override.
+    /// Keys that aren't known are warned about and ignored, so a typo doesn't quietly fall back to a default.
+    /// Any bad value is reported along with the key it belongs to and the file or `--set` it came from.
+    pub fn read_site_config(
+        input_dir: &Path,
+        config_args: &ConfigArgs,
+    ) -> Result<SiteConfig, BuildError> {
+        let config_path = find_config_file(input_dir, "config")?;
+        let mut value = read_config_value(&config_path).map_err(|message| BuildError::Config {
+            path: config_path.clone(),
+            message,
+        })?;
+        let mut sources = ConfigSources {
+            config_path,
+            env: None,
+            settings: config_args
+                .overrides
+                .iter()
+                .map(|(key, text)| Setting {
+                    key: key.clone(),
+                    text: text.clone(),
+                    value: parse_setting(text),
+                })
+                .collect(),
+        };
+        if let Some(env) = &config_args.env {
+            let env_path = find_config_file(input_dir, &format!("config.{}", env))?;
+            let overlay = read_config_value(&env_path).map_err(|message| BuildError::Config {
+                path: env_path.clone(),
+                message,
+            })?;
+            merge_values(&mut value, overlay.clone());
+            sources.env = Some((env_path, overlay));
+        }
+
+        loop {
+            let mut merged = value.clone();
+            for setting in &sources.settings {
+                merge_values(&mut merged, setting.overlay());
+            }
+
+            let mut unknown_keys = Vec::new();
+            let mut record_unknown_key =
+                |path: serde_ignored::Path| unknown_keys.push(key_path(&path));
+            let deserializer = serde_ignored::Deserializer::new(merged, &mut record_unknown_key);
+            let error = match serde_path_to_error::deserialize::<_, ConfigFile>(deserializer) {
+                Ok(file) => {
+                    for key in unknown_keys {
+                        eprintln!(
+                            "Warning: {}: unknown key `{}` is ignored",
+                            sources.describe(&key),
+                            key
+                        );
+                    }
+                    return file
+                        .into_site_config()
+                        .map_err(|(key, message)| sources.error(key, message));
+                }
+                Err(error) => error,
+            };
+
+            let path = error.path().to_string();
+            // A `--set` value that reads as a number or a mapping, like `2024` or `Staging: do not share`,
+            // is meant as the text it was written as when the key wants text, so try that before giving up
+            if let Some(setting) = sources
+                .settings
+                .iter_mut()
+                .rev()
+                .find(|setting| setting.key == path && !setting.value.is_string())
+            {
+                setting.value = Value::String(setting.text.clone());
+                continue;
+            }
+            return Err(match path.as_str() {
+                "." => sources.error("", error.inner().to_string()),
+                path => sources.error(path, format!("`{}`: {}", path, error.inner())),
+            });
+        }
+    }
+}
+
+/// Setting
+///
+/// One `--set key=value` override, with the value as it was typed and as it is currently read.
+struct Setting {
+    key: String,
+    text: String,
+    value: Value,
+}
+
+impl Setting {
+    /// overlay
+    ///
+    /// The override as a config to merge over the others, with a dotted key like `a.b` made into nested mappings.
+    fn overlay(&self) -> Value {
+        self.key
+            .split('.')
+            .rev()
+            .fold(self.value.clone(), |inner, part| {
+                Value::Object(Map::from_iter([(part.to_string(), inner)]))
+            })
+    }
+}
+
+/// ConfigSources
+///
+/// Everything the config was put together from, to tell which of them a bad or unknown key came from.
+struct ConfigSources {
+    config_path: PathBuf,
+    env: Option<(PathBuf, Value)>,
+    settings: Vec<Setting>,
+}
+
+impl ConfigSources {
+    /// describe
+    ///
+    /// Names where the value of a key, like `sections[0].title`, came from: the last `--set` of it,
+    /// otherwise the environment's config if it has the key, otherwise the main config.
+    fn describe(&self, key: &str) -> String {
+        match self.setting_for(key) {
+            Some(setting) => format!("--set {}", setting.key),
+            None => self.file_for(key).display().to_string(),
+        }
+    }
+
+    /// error
+    ///
+    /// A config error, pointing at wherever the value of the key came from.
+    fn error(&self, key: &str, message: String) -> BuildError {
+        match self.setting_for(key) {
+            Some(setting) => BuildError::Message(format!("--set {}: {}", setting.key, message)),
+            None => BuildError::Config {
+                path: self.file_for(key).to_path_buf(),
+                message,
+            },
+        }
+    }
+
+    /// setting_for
+    ///
+    /// The last `--set` of the key, of anything holding it, like `sections` for `sections[0].title`,
+    /// or of anything inside it, like `theme.dark` for an unknown `theme`.
+    fn setting_for(&self, key: &str) -> Option<&Setting> {
+        if key.is_empty() {
+            return None;
+        }
+        let holds = |outer: &str, inner: &str| {
+            inner
+                .strip_prefix(outer)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+        };
+        self.settings
+            .iter()
+            .rev()
+            .find(|setting| holds(&setting.key, key) || holds(key, &setting.key))
+    }
+
+    /// file_for
+    ///
+    /// The config file the key was read from, the environment's one when it sets the key.
+    fn file_for(&self, key: &str) -> &Path {
+        match &self.env {
+            Some((env_path, overlay)) if !key.is_empty() && value_has_key(overlay, key) => env_path,
+            _ => &self.config_path,
+        }
+    }
+}
+
+/// value_has_key
+///
+/// Whether a config sets a key, written like `sections[0].title`. Lists are replaced as a whole when configs
+/// are merged, so a config that sets a list sets everything in it.
+fn value_has_key(value: &Value, key: &str) -> bool {
+    let mut value = value;
+    for part in key.split('.') {
+        let name = part.split('[').next().unwrap_or(part);
+        match value {
+            Value::Object(map) => match map.get(name) {
+                Some(inner) => value = inner,
+                None => return false,
+            },
+            _ => return true,
+        }
+    }
+    true
+}
+
+/// The names the site config can be saved under, in the order they are looked for.
+pub const CONFIG_FILES: [&str; 2] = ["config.yaml", "config.toml"];
+
+/// ConfigFile
+///
+/// The site config as written, before the defaults that depend on other keys are filled in and it is checked.
+#[derive(Deserialize)]
+struct ConfigFile {
+    title: String,
+    url: String,
+    description: String,
+    base_path: Option<String>,
+    author: Option<String>,
+    #[serde(default = "default_feeds")]
+    feeds: Vec<FeedFormat>,
+    #[serde(default)]
+    feed_content: FeedContent,
+    feed_items: Option<NonZeroUsize>,
+    #[serde(default = "default_excerpt_words")]
+    excerpt_words: NonZeroUsize,
+    #[serde(default)]
+    previews: Vec<Listing>,
+    #[serde(default)]
+    links: LinkMode,
+    #[serde(default = "default_permalink")]
+    permalink: String,
+    #[serde(default)]
+    redirect_rules: Vec<RedirectRules>,
+    #[serde(default)]
+    sections: Vec<Section>,
+    #[serde(default)]
+    pages: Vec<String>,
+    #[serde(default)]
+    taxonomies: Vec<Taxonomy>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_feeds() -> Vec<FeedFormat> {
+    FeedFormat::ALL.to_vec()
+}
+
+fn default_excerpt_words() -> NonZeroUsize {
+    NonZeroUsize::new(50).unwrap()
+}
+
+fn default_permalink() -> String {
+    String::from(DEFAULT_PERMALINK)
+}
+
+impl ConfigFile {
+    /// into_site_config
+    ///
+    /// Fills in the defaults that depend on other keys and checks the values serde can't.
+    /// Errors come with the key they are about, to tell where its value came from.
+    fn into_site_config(self) -> Result<SiteConfig, (&'static str, String)> {
+        validate_permalink(&self.permalink).map_err(|e| ("permalink", e))?;
+        let sections = validate_sections(self.sections).map_err(|e| ("sections", e))?;
+        let pages = validate_pages(self.pages).map_err(|e| ("pages", e))?;
+        let taxonomies =
+            validate_taxonomies(self.taxonomies, &sections).map_err(|e| ("taxonomies", e))?;
+
+        let base_path = match &self.base_path {
+            Some(base_path) => normalise_base_path(base_path),
+            None => normalise_base_path(url_path(&self.url)),
+        };
+        Ok(SiteConfig {
+            author: self.author.unwrap_or_else(|| self.title.clone()),
+            title: self.title,
+            url: self.url,
+            base_path,
+            description: self.description,
+            feeds: self.feeds,
+            feed_content: self.feed_content,
+            feed_items: self.feed_items.map(NonZeroUsize::get),
+            excerpt_words: self.excerpt_words.get(),
+            previews: self.previews,
+            links: self.links,
+            permalink: self.permalink,
+            redirect_rules: self.redirect_rules,
+            sections,
+            pages,
+            taxonomies,
+        })
+    }
+}
+
+/// The formats a config file can be written in, in the order they are looked for.
+const CONFIG_EXTENSIONS: [&str; 2] = ["yaml", "toml"];
+
+/// find_config_file
+///
+/// The config file with the given name in the input directory, in any of the formats.
+/// Having both a `.yaml` and a `.toml` one is an error, as it wouldn't be clear which one is in use.
+fn find_config_file(input_dir: &Path, name: &str) -> Result<PathBuf, BuildError> {
+    let file_names: Vec<String> = CONFIG_EXTENSIONS
+        .iter()
+        .map(|extension| format!("{}.{}", name, extension))
+        .collect();
+    let found: Vec<PathBuf> = file_names
+        .iter()
+        .map(|file_name| input_dir.join(file_name))
+        .filter(|path| path.is_file())
+        .collect();
+    match found.as_slice() {
+        [path] => Ok(path.clone()),
+        [] => Err(BuildError::Config {
+            path: input_dir.join(&file_names[0]),
+            message: format!("missing config, expected one of: {}", file_names.join(", ")),
+        }),
+        _ => Err(BuildError::Config {
+            path: input_dir.to_path_buf(),
+            message: format!(
+                "found both {}, remove one of them",
+                file_names.join(" and ")
+            ),
+        }),
+    }
+}
+
+/// merge_values
+///
+/// Merges one config over another. Mappings are merged key by key, anything else is replaced outright,
+/// so an overlay's `feeds` list replaces the whole list rather than adding to it.
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// parse_setting
+///
+/// Reads the value of a `--set` override as YAML, so numbers, `true` and lists like `[rss, atom]` work as they would in the file.
+/// Anything that doesn't parse, and an empty value, is taken as text, while `null` unsets the key.
+/// A value that is read as something else for a key that wants text is taken as text in the end, see `read_site_config`.
+fn parse_setting(setting: &str) -> Value {
+    match YamlLoader::load_from_str(setting) {
+        Ok(documents) => match documents.first() {
+            Some(yaml) => yaml_to_value(yaml),
+            None => Value::String(setting.to_string()),
+        },
+        Err(_) => Value::String(setting.to_string()),
+    }
+}
+
+/// read_config_value
+///
+/// Parses a YAML or TOML config file into one tree of values, so both are deserialised the same way.
+fn read_config_value(config_path: &Path) -> Result<Value, String> {
+    let file_contents = read_file(config_path).map_err(|e| e.to_string())?;
+    if config_path.extension().is_some_and(|e| e == "toml") {
+        let table: toml::Table = toml::from_str(&file_contents).map_err(|e| {
+            let line = e
+                .span()
+                .map(|span| file_contents[..span.start].matches('\n').count() + 1)
+                .unwrap_or(1);
+            format!(
+                "unable to parse config on line {}: {}",
+                line,
+                e.message().replace('\n', ", ")
+            )
+        })?;
+        return serde_json::to_value(table).map_err(|e| e.to_string());
+    }
+
+    let documents = YamlLoader::load_from_str(&file_contents)
+        .map_err(|e| format!("unable to parse config: {}", e))?;
+    match documents.first() {
+        Some(yaml @ Yaml::Hash(_)) => Ok(yaml_to_value(yaml)),
+        Some(_) => Err(String::from(
+            "the config should be a mapping of keys to values",
+        )),
+        None => Err(String::from("the config file is empty")),
+    }
+}
+
+/// key_path
+///
+/// Writes the path of a key the way errors name it, like `sections[0].title`.
+fn key_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}[{}]", key_path(parent), index),
+        serde_ignored::Path::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => key_path(parent),
+    }
+}
+
+/// yaml_to_value
+///
+/// Converts parsed YAML into a tree of values serde can deserialise from.
+/// Keys that aren't text, like `2023:`, are kept as their text.
+fn yaml_to_value(yaml: &Yaml) -> Value {
+    match yaml {
+        Yaml::String(text) => Value::String(text.clone()),
+        Yaml::Integer(number) => Value::Number(Number::from(*number)),
+        Yaml::Real(number) => number
+            .parse()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(number.clone())),
+        Yaml::Boolean(boolean) => Value::Bool(*boolean),
+        Yaml::Array(items) => Value::Array(items.iter().map(yaml_to_value).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.iter()
+                .map(|(key, value)| {
+                    let key = match yaml_to_value(key) {
+                        Value::String(key) => key,
+                        other => other.to_string(),
+                    };
+                    (key, yaml_to_value(value))
                 })
+                .collect::<Map<String, Value>>(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// url_origin
+///
+/// The scheme and host of a URL, `https://example.com/notes` gives `https://example.com`.
+fn url_origin(url: &str) -> &str {
+    let host_start = url.find("://").map(|i| i + 3).unwrap_or(0);
+    match url[host_start..].find('/') {
+        Some(path_start) => &url[..host_start + path_start],
+        None => url,
+    }
+}
+
+/// url_path
+///
+/// Everything in a URL after its host, `https://example.com/notes` gives `/notes`.
+fn url_path(url: &str) -> &str {
+    &url[url_origin(url).len()..]
+}
+
+/// Base paths are kept with a leading slash and no trailing one, so they can go straight in front of a path.
+fn normalise_base_path(base_path: &str) -> String {
+    let trimmed = base_path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// validate_sections
+///
+/// Checks every section has a folder of its own at the top of the input directory, and titles the ones without a title.
+fn validate_sections(mut sections: Vec<Section>) -> Result<Vec<Section>, String> {
+    for i in 0..sections.len() {
+        let folder = sections[i].folder.trim_matches('/').to_string();
+        if folder.is_empty() || folder.contains(['/', '\\']) || folder.starts_with('.') {
+            return Err(format!(
+                "`sections[{}].folder`: `{}` should be the name of a folder at the top of the input directory",
+                i, folder
+            ));
+        }
+        if RESERVED_NAMES.contains(&folder.as_str()) {
+            return Err(format!(
+                "`sections[{}].folder`: `{}` can't be a section as the site already uses that name",
+                i, folder
+            ));
+        }
+        if sections[..i].iter().any(|other| other.folder == folder) {
+            return Err(format!(
+                "`sections[{}].folder`: the section `{}` is listed twice",
+                i, folder
+            ));
+        }
+
+        let section = &mut sections[i];
+        if section.title.is_empty() {
+            section.title = folder.clone();
+        }
+        section.folder = folder;
+    }
+    Ok(sections)
+}
+
+/// validate_taxonomies
+///
+/// Checks every taxonomy has a name of its own that can be used for a folder, and titles the ones without a title.
+fn validate_taxonomies(
+    mut taxonomies: Vec<Taxonomy>,
+    sections: &[Section],
+) -> Result<Vec<Taxonomy>, String> {
+    for i in 0..taxonomies.len() {
+        let name = taxonomies[i].name.trim().to_string();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "`taxonomies[{}].name`: `{}` should only use letters, numbers, - and _",
+                i, name
+            ));
+        }
+        if RESERVED_NAMES.contains(&name.as_str())
+            || sections.iter().any(|section| section.folder == name)
+        {
+            return Err(format!(
+                "`taxonomies[{}].name`: `{}` can't be a taxonomy as the site already uses that name",
+                i, name
+            ));
+        }
+        if taxonomies[..i].iter().any(|other| other.name == name) {
+            return Err(format!(
+                "`taxonomies[{}].name`: the taxonomy `{}` is listed twice",
+                i, name
+            ));
+        }
+
+        let taxonomy = &mut taxonomies[i];
+        if taxonomy.title.is_empty() {
+            taxonomy.title = name.clone();
+        }
+        taxonomy.name = name;
+    }
+    Ok(taxonomies)
+}
+
+/// validate_pages
+///
+/// Checks every page is a markdown file within the input directory.
+fn validate_pages(pages: Vec<String>) -> Result<Vec<String>, String> {
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let page = page.trim_start_matches("./");
+            if page.ends_with(".md")
+                && !page.starts_with('/')
+                && !page.split('/').any(|part| part == "..")
+            {
+                Ok(page.to_string())
+            } else {
+                Err(format!(
+                    "`pages[{}]`: `{}` should be the path of a .md file in the input directory",
+                    i, page
+                ))
             }
-            Err(e) => {
-                println!("Error finding config file: {}", e);
-                Err(e)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// An input directory holding the given files, removed once the test is done.
+    fn input_dir(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (file_name, contents) in files {
+            fs::write(dir.path().join(file_name), contents).unwrap();
+        }
+        dir
+    }
+
+    const CONFIG: &str = "title: Notes\nurl: https://ex.com/notes\ndescription: A blog\n";
+
+    fn settings(settings: &[(&str, &str)]) -> ConfigArgs {
+        ConfigArgs {
+            env: None,
+            overrides: settings
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn mappings_are_merged_key_by_key() {
+        let mut base = json!({"title": "Notes", "nested": {"a": 1, "b": 2}});
+        merge_values(&mut base, json!({"nested": {"b": 3, "c": 4}}));
+        assert_eq!(
+            base,
+            json!({"title": "Notes", "nested": {"a": 1, "b": 3, "c": 4}})
+        );
+    }
+
+    #[test]
+    fn lists_and_values_are_replaced() {
+        let mut base = json!({"feeds": ["rss", "atom"], "title": "Notes"});
+        merge_values(&mut base, json!({"feeds": ["json"], "title": null}));
+        assert_eq!(base, json!({"feeds": ["json"], "title": null}));
+    }
+
+    #[test]
+    fn settings_are_read_like_yaml() {
+        assert_eq!(parse_setting("[rss, atom]"), json!(["rss", "atom"]));
+        assert_eq!(parse_setting("true"), json!(true));
+        assert_eq!(parse_setting("20"), json!(20));
+        assert_eq!(parse_setting("null"), Value::Null);
+        assert_eq!(parse_setting("text"), json!("text"));
+        assert_eq!(parse_setting(""), json!(""));
+        assert_eq!(parse_setting("[unclosed"), json!("[unclosed"));
+    }
+
+    #[test]
+    fn settings_for_text_keys_are_taken_as_written() {
+        let dir = input_dir(&[("config.yaml", CONFIG)]);
+        let config = SiteConfig::read_site_config(
+            dir.path(),
+            &settings(&[
+                ("title", "2024"),
+                ("description", "Staging: do not share"),
+                ("feed_items", "5"),
+                ("feeds", "[rss]"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.title, "2024");
+        assert_eq!(config.description, "Staging: do not share");
+        assert_eq!(config.feed_items, Some(5));
+        assert_eq!(config.feeds, vec![FeedFormat::Rss]);
+    }
+
+    #[test]
+    fn bad_settings_are_blamed_on_the_setting() {
+        let dir = input_dir(&[("config.yaml", CONFIG)]);
+        let error = SiteConfig::read_site_config(dir.path(), &settings(&[("feeds", "rss")]))
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("--set feeds: `feeds`:"), "{}", error);
+    }
+
+    #[test]
+    fn bad_values_are_blamed_on_the_file_they_came_from() {
+        let dir = input_dir(&[
+            ("config.yaml", CONFIG),
+            ("config.staging.yaml", "permalink: /posts/\n"),
+        ]);
+        let config_args = ConfigArgs {
+            env: Some(String::from("staging")),
+            overrides: Vec::new(),
+        };
+        let error = SiteConfig::read_site_config(dir.path(), &config_args).unwrap_err();
+        match error {
+            BuildError::Config { path, .. } => {
+                assert_eq!(path, dir.path().join("config.staging.yaml"))
             }
+            other => panic!("unexpected error: {}", other),
         }
     }
+
+    #[test]
+    fn keys_are_found_in_overlays() {
+        let overlay = json!({"url": "x", "sections": [{"folder": "notes"}], "nested": {"a": 1}});
+        assert!(value_has_key(&overlay, "url"));
+        assert!(value_has_key(&overlay, "sections[0].folder"));
+        assert!(value_has_key(&overlay, "nested.a"));
+        assert!(!value_has_key(&overlay, "nested.b"));
+        assert!(!value_has_key(&overlay, "title"));
+    }
 }
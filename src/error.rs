@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong while building the site.
+/// Each error carries the file it came from, so a bad post can be found and fixed straight away.
+#[derive(Debug)]
+pub enum BuildError {
+    /// Reading, writing or copying a file failed.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The front matter of a post couldn't be parsed or holds a bad value.
+    /// Line and column are 1-based and point into the post file itself.
+    FrontMatter {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A layout couldn't be parsed or rendered. The line is 1-based, when it is known.
+    Template {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// The site config is missing, malformed or missing a required key.
+    Config { path: PathBuf, message: String },
+    /// Anything else that stops a command, such as bad input on the command line.
+    Message(String),
+}
+
+impl BuildError {
+    pub fn io(path: &Path, source: std::io::Error) -> BuildError {
+        BuildError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            BuildError::FrontMatter {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            BuildError::Template {
+                path,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            BuildError::Template { path, message, .. } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            BuildError::Config { path, message } => write!(f, "{}: {}", path.display(), message),
+            BuildError::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Lets a single error be returned with `?` from anything that collects errors.
+impl From<BuildError> for Vec<BuildError> {
+    fn from(error: BuildError) -> Self {
+        vec![error]
+    }
+}
+
+/// report_errors
+///
+/// Prints every collected error together, followed by a count.
+pub fn report_errors(errors: &[BuildError]) {
+    for error in errors {
+        eprintln!("Error: {}", error);
+    }
+    match errors.len() {
+        1 => eprintln!("Failed with 1 error."),
+        count => eprintln!("Failed with {} errors.", count),
+    }
+}
@@ -0,0 +1,35 @@
+use std::cmp::Reverse;
+
+use crate::{
+    config::{FeedContent, SiteConfig},
+    posts::Post,
+    urls::make_urls_absolute,
+};
+
+/// feed_posts
+///
+/// The posts a feed should include: newest first, and no more than `feed_items` of them.
+/// Posts from a section that stays out of the feeds are left out, of the site's feeds and of each term's alike.
+pub fn feed_posts<'a>(posts: &'a [Post], config: &SiteConfig) -> Vec<&'a Post> {
+    let mut sorted_posts: Vec<&Post> = posts.iter().filter(|post| post.in_feeds).collect();
+    sorted_posts.sort_by_key(|post| Reverse(post.metadata.date));
+    if let Some(feed_items) = config.feed_items {
+        sorted_posts.truncate(feed_items);
+    }
+    sorted_posts
+}
+
+/// feed_content
+///
+/// The HTML a feed item carries for a post: just the article, without the page's head, header or footer,
+/// and with every link made absolute so it still works inside a feed reader.
+/// Returns `None` when the site only publishes summaries in its feeds.
+pub fn feed_content(post: &Post, config: &SiteConfig) -> Option<String> {
+    match config.feed_content {
+        FeedContent::Full => {
+            let page_url = post.path.strip_prefix("./").unwrap_or(&post.path);
+            Some(make_urls_absolute(&post.body, page_url, config))
+        }
+        FeedContent::Summary => None,
+    }
+}
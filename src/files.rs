@@ -1,45 +1,121 @@
-use std::{fs, io::Error, path::Path};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Component, Path, PathBuf},
+};
 
-pub fn read_file(path: &Path) -> Result<String, Error> {
-    fs::read_to_string(path)
+use crate::{
+    cache::{hash_bytes, BuildCache},
+    error::BuildError,
+    manifest::Manifest,
+};
+
+pub fn read_file(path: &Path) -> Result<String, BuildError> {
+    fs::read_to_string(path).map_err(|e| BuildError::io(path, e))
+}
+
+/// find_markdown_files
+///
+/// Lists every `.md` file in a directory and all of the directories within it, sorted by path.
+/// Hidden files and folders, like an editor's `.obsidian` folder, are skipped.
+pub fn find_markdown_files(dir: &Path) -> Result<Vec<PathBuf>, BuildError> {
+    let mut files = Vec::new();
+    for entry_result in fs::read_dir(dir).map_err(|e| BuildError::io(dir, e))? {
+        let entry = entry_result.map_err(|e| BuildError::io(dir, e))?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(find_markdown_files(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "md") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
 }
 
-pub fn copy_dir_to(src_dir: &Path, dest_dir: &Path) -> Result<(), Error> {
+/// copy_dir_to
+///
+/// Recursively copies a directory, skipping any file that hasn't changed since the last build.
+/// Every file copied, or already up to date, is recorded in the manifest.
+pub fn copy_dir_to(
+    src_dir: &Path,
+    dest_dir: &Path,
+    cache: &mut BuildCache,
+    manifest: &mut Manifest,
+) -> Result<(), BuildError> {
     if !dest_dir.exists() {
-        fs::create_dir_all(dest_dir)?;
+        fs::create_dir_all(dest_dir).map_err(|e| BuildError::io(dest_dir, e))?;
     }
-    for entry_result in fs::read_dir(src_dir)? {
-        let entry = entry_result?;
-        let file_type = entry.file_type()?;
+    for entry_result in fs::read_dir(src_dir).map_err(|e| BuildError::io(src_dir, e))? {
+        let entry = entry_result.map_err(|e| BuildError::io(src_dir, e))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| BuildError::io(&entry.path(), e))?;
+        let dest_path = dest_dir.join(entry.file_name());
         if file_type.is_file() {
-            // Copy the file
-            fs::copy(entry.path(), dest_dir.join(entry.file_name()))?;
+            // Copy the file, unless the copy from last time is still up to date
+            let contents = fs::read(entry.path()).map_err(|e| BuildError::io(&entry.path(), e))?;
+            let key = cache.output_key(&dest_path);
+            let hash = hash_bytes(&contents);
+            cache.record(&key, hash);
+            manifest.record_path(&dest_path);
+            if cache.is_unchanged(&key, hash) && dest_path.exists() {
+                continue;
+            }
+            fs::write(&dest_path, contents).map_err(|e| BuildError::io(&dest_path, e))?;
         } else if file_type.is_dir() {
             // Recursively copy the directory
-            copy_dir_to(&entry.path(), &dest_dir.join(entry.file_name()))?;
+            copy_dir_to(&entry.path(), &dest_path, cache, manifest)?;
         }
     }
     Ok(())
 }
 
-pub fn write_to_file(directory: &Path, file_name: &str, contents: &str) -> Result<(), Error> {
+pub fn write_to_file(directory: &Path, file_name: &str, contents: &str) -> Result<(), BuildError> {
     let path = Path::new(directory).join(file_name);
-    fs::write(path, contents)?;
+    fs::write(&path, contents).map_err(|e| BuildError::io(&path, e))?;
     Ok(())
 }
 
-pub fn create_html_file_name(file_name: &str) -> Option<String> {
-    let path = Path::new(file_name);
-    let file_stem = path.file_stem()?;
-    let new_file_name = file_stem.to_string_lossy().into_owned() + ".html";
-    Some(new_file_name)
-}
+/// remove_outputs
+///
+/// Removes files a build wrote, given relative to the output directory, along with the folders that leaves empty.
+/// Only paths inside the output directory are touched, whatever the list says. Returns how many files were removed.
+pub fn remove_outputs<'a>(
+    output_dir: &Path,
+    file_names: impl IntoIterator<Item = &'a String>,
+) -> Result<usize, BuildError> {
+    let mut removed_files = 0;
+    let mut folders = BTreeSet::new();
+    for file_name in file_names {
+        let relative_path = Path::new(file_name);
+        if !relative_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            continue;
+        }
+        let path = output_dir.join(relative_path);
+        if path.is_file() {
+            fs::remove_file(&path).map_err(|e| BuildError::io(&path, e))?;
+            removed_files += 1;
+        }
+        folders.extend(
+            relative_path
+                .ancestors()
+                .skip(1)
+                .filter(|folder| !folder.as_os_str().is_empty())
+                .map(Path::to_path_buf),
+        );
+    }
 
-pub fn prepend_go_up_folder_to_path(path: &str, num: i8) -> String {
-    let mut new_path = String::new();
-    for _ in 0..num {
-        new_path.push_str("../");
+    // Sub folders sort after their parent, so going backwards empties each folder before it is reached.
+    // Removing a folder that still holds something fails, which is exactly what should happen to it.
+    for folder in folders.iter().rev() {
+        let _ = fs::remove_dir(output_dir.join(folder));
     }
-    new_path.push_str(path);
-    new_path
+    Ok(removed_files)
 }
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::SiteConfig;
+use crate::error::BuildError;
+use crate::feed::{feed_content, feed_posts};
+use crate::files::write_to_file;
+use crate::posts::Post;
+
+/// The top level of a JSON Feed 1.1 document, see https://jsonfeed.org/version/1.1
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: String,
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    summary: String,
+    date_published: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+/// build_json_feed
+///
+/// Writes a JSON Feed 1.1 of every post to feed.json, for tools that would rather not parse XML.
+pub fn build_json_feed(
+    output_dir: &Path,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
+    let items = feed_posts(posts, config)
+        .into_iter()
+        .map(|post| {
+            let content_html = feed_content(post, config);
+            // Every item needs some content, which is the summary when that's all the feed carries
+            let content_text = match content_html {
+                Some(_) => None,
+                None => Some(post.metadata.summary.clone()),
+            };
+            JsonFeedItem {
+                id: post.public_link.clone(),
+                url: post.public_link.clone(),
+                title: post.metadata.title.clone(),
+                content_html,
+                content_text,
+                summary: post.metadata.summary.clone(),
+                date_published: post.metadata.published_at().to_rfc3339(),
+                tags: post.metadata.categories.clone(),
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: config.title.clone(),
+        home_page_url: config.site_url(),
+        feed_url: config.public_url("feed.json"),
+        description: config.description.clone(),
+        authors: vec![JsonFeedAuthor {
+            name: config.author.clone(),
+        }],
+        items,
+    };
+
+    // Serialising plain strings and lists can't fail
+    let output = serde_json::to_string_pretty(&feed).unwrap();
+    write_to_file(output_dir, "feed.json", &output)
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use minijinja::{context, Environment, Value};
+use serde::Serialize;
+
+use crate::{
+    category::{get_category_path, Category},
+    config::{Section, SiteConfig, Taxonomy},
+    error::BuildError,
+    files::read_file,
+    posts::Post,
+    templates::{add_head, get_footer, get_header, head_contents, wrap_in_header_and_footer},
+    urls::resolve_urls,
+};
+
+/// Layouts are read from this folder in the input directory.
+const LAYOUTS_FOLDER: &str = "layouts";
+
+/// Wraps every page: `site`, `title`, `head`, `header`, `footer` and `content`.
+pub const BASE_LAYOUT: &str = "base.html";
+/// The article on a post page: `site`, `post` and `content`.
+pub const POST_LAYOUT: &str = "post.html";
+/// The all posts page and the listing page of each section: `site`, `posts` and `content`,
+/// plus `section` on a section's page.
+pub const LIST_LAYOUT: &str = "list.html";
+/// The page of each category, or term of another taxonomy: `site`, `taxonomy`, `category`, `posts` and `content`.
+pub const CATEGORY_LAYOUT: &str = "category.html";
+/// The home page: `site`, `posts` and `content`.
+pub const INDEX_LAYOUT: &str = "index.html";
+/// A standalone page: `site`, `page` and `content`.
+pub const PAGE_LAYOUT: &str = "page.html";
+
+/// Layouts
+///
+/// The page layouts a theme provides in the `layouts` folder of the input directory, as Jinja templates.
+/// Every layout is optional: a page without one is built the way it always has been,
+/// and that default HTML is handed to the layout as `content` so it can be wrapped rather than rewritten.
+pub struct Layouts {
+    layouts_dir: PathBuf,
+    environment: Environment<'static>,
+}
+
+#[derive(Serialize)]
+struct SiteContext {
+    title: String,
+    url: String,
+    base_path: String,
+    description: String,
+    author: String,
+}
+
+#[derive(Serialize)]
+struct CategoryContext {
+    name: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct PostContext {
+    title: String,
+    /// As written in the front matter, like 2023-08-01
+    date: String,
+    /// Like 1 August 2023
+    display_date: String,
+    categories: Vec<CategoryContext>,
+    /// The terms of every other taxonomy, keyed by its name, like `post.terms.tags`
+    terms: BTreeMap<String, Vec<CategoryContext>>,
+    summary: String,
+    cover: Option<String>,
+    reading_time: usize,
+    path: String,
+    url: String,
+    content: Value,
+}
+
+impl Layouts {
+    /// load
+    ///
+    /// Reads every `.html` file in the layouts folder, reporting each one that doesn't parse.
+    /// A site without a layouts folder gets no layouts.
+    pub fn load(input_dir: &Path) -> Result<Layouts, Vec<BuildError>> {
+        let layouts_dir = input_dir.join(LAYOUTS_FOLDER);
+        let mut environment = Environment::new();
+        let mut errors = Vec::new();
+
+        if layouts_dir.is_dir() {
+            let entries =
+                fs::read_dir(&layouts_dir).map_err(|e| BuildError::io(&layouts_dir, e))?;
+            for entry in entries {
+                let path = entry.map_err(|e| BuildError::io(&layouts_dir, e))?.path();
+                if !path.is_file() || path.extension().is_none_or(|e| e != "html") {
+                    continue;
+                }
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let source = read_file(&path)?;
+                if let Err(e) = environment.add_template_owned(name, source) {
+                    errors.push(template_error(&layouts_dir, e));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Layouts {
+                layouts_dir,
+                environment,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// has
+    ///
+    /// Whether the theme provides the given layout.
+    pub fn has(&self, name: &str) -> bool {
+        self.environment.get_template(name).is_ok()
+    }
+
+    /// require
+    ///
+    /// Fails unless the theme provides the given layout, for layouts the config names.
+    pub fn require(&self, name: &str) -> Result<(), BuildError> {
+        if self.has(name) {
+            Ok(())
+        } else {
+            Err(BuildError::Template {
+                path: self.layouts_dir.join(name),
+                line: None,
+                message: String::from("the config asks for this layout, but it doesn't exist"),
+            })
+        }
+    }
+
+    /// render
+    ///
+    /// Renders a layout, pointing any error at the line of the layout it came from.
+    pub fn render(&self, name: &str, context: Value) -> Result<String, BuildError> {
+        self.environment
+            .get_template(name)
+            .and_then(|template| template.render(context))
+            .map_err(|e| template_error(&self.layouts_dir, e))
+    }
+
+    /// render_page
+    ///
+    /// Turns the content of a page into the whole page, through the base layout when there is one,
+    /// or the header, footer and head otherwise.
+    /// Every link to the site is then resolved for where the page is written,
+    /// `levels_down` folders below the output directory.
+    pub fn render_page(
+        &self,
+        input_dir: &Path,
+        config: &SiteConfig,
+        title: &str,
+        head_links: &str,
+        content: &str,
+        levels_down: usize,
+    ) -> Result<String, BuildError> {
+        let page = if self.has(BASE_LAYOUT) {
+            self.render(
+                BASE_LAYOUT,
+                context! {
+                    site => site_context(config),
+                    title => title,
+                    head => Value::from_safe_string(head_contents(title, head_links)),
+                    header => Value::from_safe_string(get_header(input_dir)?),
+                    footer => Value::from_safe_string(get_footer(input_dir)?),
+                    content => Value::from_safe_string(content.to_string()),
+                },
+            )?
+        } else {
+            let wrapped_content = wrap_in_header_and_footer(input_dir, content)?;
+            add_head(&wrapped_content, title, head_links)
+        };
+        Ok(resolve_urls(&page, levels_down, config.links, config))
+    }
+}
+
+/// site_context
+///
+/// The `site` variable every layout gets.
+pub fn site_context(config: &SiteConfig) -> Value {
+    Value::from_serialize(SiteContext {
+        title: config.title.clone(),
+        url: config.site_url(),
+        base_path: config.base_path.clone(),
+        description: config.description.clone(),
+        author: config.author.clone(),
+    })
+}
+
+/// post_context
+///
+/// A post as layouts see it, with its rendered body as `content`.
+pub fn post_context(post: &Post) -> Value {
+    Value::from_serialize(PostContext {
+        title: post.metadata.title.clone(),
+        date: post.metadata.date.format("%Y-%m-%d").to_string(),
+        display_date: post.metadata.date.format("%-d %B %Y").to_string(),
+        categories: post
+            .metadata
+            .categories
+            .iter()
+            .map(|category| CategoryContext {
+                name: category.clone(),
+                path: format!("./{}", get_category_path(category)),
+            })
+            .collect(),
+        terms: post
+            .metadata
+            .terms
+            .iter()
+            .map(|(taxonomy, terms)| {
+                let terms = terms
+                    .iter()
+                    .map(|term| CategoryContext {
+                        name: term.clone(),
+                        path: format!("./{}/{}", taxonomy, get_category_path(term)),
+                    })
+                    .collect();
+                (taxonomy.clone(), terms)
+            })
+            .collect(),
+        summary: post.metadata.summary.clone(),
+        cover: post.metadata.cover.clone(),
+        reading_time: post.reading_time,
+        path: post.path.clone(),
+        url: post.public_link.clone(),
+        content: Value::from_safe_string(post.body.clone()),
+    })
+}
+
+/// posts_context
+///
+/// A list of posts as layouts see it, newest first.
+pub fn posts_context(posts: &[Post]) -> Value {
+    let mut sorted_posts: Vec<&Post> = posts.iter().collect();
+    sorted_posts.sort_by_key(|post| std::cmp::Reverse(post.metadata.date));
+    Value::from(
+        sorted_posts
+            .into_iter()
+            .map(post_context)
+            .collect::<Vec<Value>>(),
+    )
+}
+
+/// category_context
+///
+/// A category as layouts see it, with the path of its feed.
+pub fn category_context(category: &Category) -> Value {
+    context! {
+        name => category.name.clone(),
+        path => format!("./{}", category.path),
+        feed_path => format!("./{}", category.feed_path()),
+    }
+}
+
+/// taxonomy_context
+///
+/// A taxonomy as layouts see it, with the path of its index page.
+pub fn taxonomy_context(taxonomy: &Taxonomy) -> Value {
+    context! {
+        name => taxonomy.name.clone(),
+        title => taxonomy.title.clone(),
+        path => format!("./{}", taxonomy.index_path()),
+    }
+}
+
+/// section_context
+///
+/// A section as layouts see it, with the path of its listing page.
+pub fn section_context(section: &Section) -> Value {
+    context! {
+        folder => section.folder.clone(),
+        title => section.title.clone(),
+        path => format!("./{}", section.listing_path()),
+    }
+}
+
+fn template_error(layouts_dir: &Path, error: minijinja::Error) -> BuildError {
+    let path = match error.name() {
+        Some(name) => layouts_dir.join(name),
+        None => layouts_dir.to_path_buf(),
+    };
+    let message = match error.detail() {
+        Some(detail) => format!("{}: {}", error.kind(), detail),
+        None => error.kind().to_string(),
+    };
+    BuildError::Template {
+        path,
+        line: error.line(),
+        message,
+    }
+}
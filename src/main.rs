@@ -1,222 +1,831 @@
 mod argparse;
+mod atom;
+mod cache;
 mod category;
 mod config;
+mod error;
+mod feed;
 mod files;
+mod json_feed;
+mod layouts;
+mod manifest;
 mod metadata;
 mod paths;
+mod permalink;
 mod posts;
+mod redirects;
 mod rss;
+mod scaffold;
+mod serve;
+mod slug;
 mod templates;
+mod urls;
 
 use std::{
     fs::{self},
-    io::Error,
-    path::Path,
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
 
-use argparse::Cli;
-use category::{create_category_list_html, Category};
+use argparse::{Cli, Command, ConfigArgs, PublishArgs, SiteArgs};
+use category::{create_category_list_html, group_by_term, CategoryPosts};
+use chrono::Local;
 use clap::Parser;
-use files::{create_html_file_name, write_to_file};
+use files::write_to_file;
+use minijinja::{context, Value};
 use paths::Paths;
+use rayon::prelude::*;
 use templates::{add_date_to_body, group_by_year_as_html};
 
 use crate::{
-    category::get_category_path,
-    config::SiteConfig,
-    files::{copy_dir_to, read_file},
+    atom::build_atom_feed,
+    cache::{hash_bytes, site_hash, BuildCache, RenderedPost, CACHE_FILE_NAME},
+    config::{FeedFormat, Listing, Section, SiteConfig, Taxonomy, CATEGORIES, CONFIG_FILES},
+    error::{report_errors, BuildError},
+    files::{copy_dir_to, find_markdown_files, read_file, remove_outputs},
+    json_feed::build_json_feed,
+    layouts::{
+        category_context, post_context, posts_context, section_context, site_context,
+        taxonomy_context, Layouts, CATEGORY_LAYOUT, INDEX_LAYOUT, LIST_LAYOUT, PAGE_LAYOUT,
+        POST_LAYOUT,
+    },
+    manifest::{Manifest, MANIFEST_FILE_NAME},
     metadata::MetaData,
-    posts::Post,
-    rss::build_rss_feed,
+    permalink::{expand_permalink, permalink_file_name},
+    posts::{create_excerpt, reading_time, Post, MORE_MARKER},
+    redirects::{alias_file_name, build_redirects},
+    rss::{build_category_rss_feed, build_rss_feed},
+    scaffold::new_post,
+    serve::serve,
+    slug::{slugify, OutputFiles},
     templates::{
-        add_head, add_recent_posts, add_title_to_body, get_index_template,
-        wrap_in_header_and_footer,
+        add_title_to_body, fill_index_template, get_index_template, wrap_in_header_and_footer,
     },
 };
 
-fn main() -> Result<(), Error> {
-    match read_args() {
-        Ok(path) => {
-            println!("Input Path: {}", path.input);
-            println!("Output Path: {}", path.output);
+fn main() -> ExitCode {
+    let command = match read_args() {
+        Ok(command) => command,
+        Err(e) => e.exit(),
+    };
+
+    let result = match command {
+        Command::Build(site) => {
+            let path = read_paths(&site);
+            build_site(
+                Path::new(&path.input),
+                Path::new(&path.output),
+                site.publish,
+                &site.config,
+            )
+        }
+        Command::Serve { site, port } => {
+            let path = read_paths(&site);
+            serve(
+                Path::new(&path.input),
+                Path::new(&path.output),
+                port,
+                site.publish,
+                &site.config,
+            )
+            .map_err(Vec::from)
+        }
+        Command::New {
+            input_dir,
+            title,
+            categories,
+            summary,
+        } => new_post(&input_dir, title, categories, summary).map_err(Vec::from),
+        Command::Check {
+            input_dir,
+            config,
+            jobs,
+        } => {
+            set_jobs(jobs);
+            check_site(&input_dir, &config)
+        }
+        Command::Clean { output_dir } => clean_site(&output_dir).map_err(Vec::from),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(errors) => {
+            report_errors(&errors);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn read_args() -> Result<Command, clap::Error> {
+    let args = Cli::try_parse()?;
+    Ok(args.command)
+}
+
+/// set_jobs
+///
+/// Caps how many threads posts are rendered with. Left alone, every CPU core is used.
+fn set_jobs(jobs: Option<usize>) {
+    if let Some(jobs) = jobs {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+        {
+            println!("Unable to limit the number of jobs: {}", e);
+        }
+    }
+}
+
+fn read_paths(site: &SiteArgs) -> Paths {
+    set_jobs(site.jobs);
+    let paths = Paths {
+        input: site.input_dir.to_str().unwrap().to_string(),
+        output: site.output_dir.to_str().unwrap().to_string(),
+    };
+    println!("Input Path: {}", paths.input);
+    println!("Output Path: {}", paths.output);
+    paths
+}
+
+/// build_site
+///
+/// Runs the whole pipeline once: copies the static folders, renders the posts and writes every
+/// generated page and feed into the output directory.
+/// Posts that aren't published yet, or any more, are left out unless `publish` lets them through.
+/// Every problem found along the way is returned together.
+pub fn build_site(
+    input_path: &Path,
+    output_path: &Path,
+    publish: PublishArgs,
+    config_args: &ConfigArgs,
+) -> Result<(), Vec<BuildError>> {
+    if !input_path.is_dir() {
+        return Err(
+            BuildError::Message(format!("{} is not a folder", input_path.display())).into(),
+        );
+    }
+    create_directory(output_path)?;
+
+    let config = SiteConfig::read_site_config(input_path, config_args)?;
+    let layouts = Layouts::load(input_path)?;
+    let mut cache = BuildCache::load(output_path, site_hash(input_path, &config));
+    let mut manifest = Manifest::load(output_path);
+
+    let posts = read_published_posts(
+        input_path,
+        "posts",
+        output_path,
+        &config,
+        &layouts,
+        &publish,
+        &mut cache,
+    )?;
+    let mut section_posts = Vec::new();
+    for section in &config.sections {
+        section_posts.push(read_published_posts(
+            input_path,
+            &section.folder,
+            output_path,
+            &config,
+            &layouts,
+            &publish,
+            &mut cache,
+        )?);
+    }
+    let every_post: Vec<Post> = posts
+        .iter()
+        .chain(section_posts.iter().flatten())
+        .cloned()
+        .collect();
+    // Section posts stay off the home page, but share categories and other terms with the rest of the site
+    let taxonomies = group_taxonomies(&every_post, &config);
+
+    // Everything that shares a file name is found before anything is written, so nothing is half replaced
+    let output_files = check_output_files(&every_post, &taxonomies, &config)?;
 
-            let input_path = Path::new(&path.input);
-            let output_path = Path::new(&path.output);
+    build_images_folder(input_path, output_path, &mut cache, &mut manifest)?;
+    build_style_folder(input_path, output_path, &mut cache, &mut manifest)?;
 
-            create_directory(path.output.as_str()).expect("Unable to create directory");
+    write_posts(output_path, &every_post)?;
 
-            if input_path.is_dir() {
-                let config = SiteConfig::read_site_config(input_path)?;
+    build_main_page(
+        input_path,
+        output_path,
+        &posts,
+        &taxonomies[0].1,
+        &config,
+        &layouts,
+    )?;
 
-                build_images_folder(input_path, output_path)?;
-                build_style_folder(input_path, output_path)?;
+    build_all_posts_page(input_path, output_path, &posts, &config, &layouts)?;
 
-                let (posts, categories) =
-                    build_content_folder(&input_path, "posts", output_path, &config)?;
+    for (taxonomy, terms) in &taxonomies {
+        build_taxonomy_index_page(input_path, output_path, taxonomy, terms, &config, &layouts)?;
+        build_term_pages(input_path, output_path, taxonomy, terms, &config, &layouts)?;
+    }
+
+    for (section, section_posts) in config.sections.iter().zip(&section_posts) {
+        build_section_page(
+            input_path,
+            output_path,
+            section,
+            section_posts,
+            &config,
+            &layouts,
+        )?;
+    }
 
-                build_main_page(input_path, output_path, &posts, &config.title)?;
+    build_pages(input_path, output_path, &config, &layouts)?;
 
-                build_all_posts_page(input_path, output_path, &posts, &config.title)?;
+    build_redirects(output_path, &every_post, &config)?;
 
-                build_categories_index_page(input_path, output_path, &categories, &config.title)?;
+    if config.feeds.contains(&FeedFormat::Rss) {
+        build_rss_feed(output_path, &every_post, &config)?;
+    }
+    if config.feeds.contains(&FeedFormat::Atom) {
+        build_atom_feed(output_path, &every_post, &config)?;
+    }
+    if config.feeds.contains(&FeedFormat::Json) {
+        build_json_feed(output_path, &every_post, &config)?;
+    }
 
-                build_category_pages(input_path, output_path, &categories, &config.title)?;
+    for file_name in &output_files {
+        manifest.record(file_name);
+    }
+    manifest.remove_stale_files()?;
+    manifest.save()?;
+    cache.save()?;
+    println!("Done!");
+    Ok(())
+}
 
-                build_rss_feed(output_path, posts, &config);
+/// check_site
+///
+/// Reads the config, templates and every post exactly as a build would, but writes nothing.
+pub fn check_site(input_path: &Path, config_args: &ConfigArgs) -> Result<(), Vec<BuildError>> {
+    println!("Checking {}...", input_path.display());
+    if !input_path.is_dir() {
+        return Err(
+            BuildError::Message(format!("{} is not a folder", input_path.display())).into(),
+        );
+    }
+    let mut errors = Vec::new();
 
-                println!("Done!");
+    // The home page is optional, as it is for a build, but one that is there has to be usable
+    let index_path = input_path.join("index.html");
+    if index_path.is_file() {
+        let index_template = get_index_template(input_path);
+        if let Err(e) = index_template.and_then(|index_template| {
+            fill_index_template(&index_path, &index_template, &[], &Vec::new(), false)
+        }) {
+            errors.push(e);
+        }
+    }
+    if let Err(e) = wrap_in_header_and_footer(input_path, "") {
+        errors.push(e);
+    }
+
+    let layouts = Layouts::load(input_path).map_err(|layout_errors| errors.extend(layout_errors));
+    let config = SiteConfig::read_site_config(input_path, config_args).map_err(|e| errors.push(e));
+
+    // Posts can only be read once the config and layouts they depend on are known to be good
+    if let (Ok(config), Ok(layouts)) = (config, layouts) {
+        let mut posts = Vec::new();
+        let folders = std::iter::once("posts").chain(
+            config
+                .sections
+                .iter()
+                .map(|section| section.folder.as_str()),
+        );
+        for folder in folders {
+            match read_content_folder(
+                input_path,
+                folder,
+                Path::new(""),
+                &config,
+                &layouts,
+                &mut BuildCache::default(),
+            ) {
+                Ok(folder_posts) => posts.extend(folder_posts),
+                Err(post_errors) => errors.extend(post_errors),
             }
         }
-        Err(e) => {
-            println!("Error: {}", e);
+        for page in &config.pages {
+            if let Err(page_errors) = read_page(input_path, page, &config, &layouts) {
+                errors.extend(page_errors);
+            }
+        }
+
+        if errors.is_empty() {
+            let taxonomies = group_taxonomies(&posts, &config);
+            match check_output_files(&posts, &taxonomies, &config) {
+                Ok(_) => {
+                    let today = Local::now().date_naive();
+                    let unpublished_posts = posts
+                        .iter()
+                        .filter(|post| !post.metadata.is_published(&PublishArgs::default(), today))
+                        .count();
+                    println!(
+                        "Found {} posts in {} categories, {} of them not published, and {} pages.",
+                        posts.len(),
+                        taxonomies.first().map_or(0, |(_, terms)| terms.len()),
+                        unpublished_posts,
+                        config.pages.len()
+                    )
+                }
+                Err(file_errors) => errors.extend(file_errors),
+            }
         }
     }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    println!("Everything looks good!");
     Ok(())
 }
 
-fn read_args() -> Result<Paths, Error> {
-    let args = Cli::parse();
+/// clean_site
+///
+/// Removes every file the last build wrote into the output directory, as listed in its manifest, along with the
+/// folders that leaves empty. Anything else in there, like a `.git` folder, is left alone.
+/// Refuses a folder without a manifest, or one that contains the current directory or looks like an input folder,
+/// so a typo can't wipe out anything that wasn't generated.
+pub fn clean_site(output_path: &Path) -> Result<(), BuildError> {
+    if !output_path.exists() {
+        println!("Nothing to clean at {}", output_path.display());
+        return Ok(());
+    }
 
-    let paths = Paths {
-        input: args.input_dir.to_str().unwrap().to_string(),
-        output: args.output_dir.to_str().unwrap().to_string(),
+    let output_path = fs::canonicalize(output_path).map_err(|e| BuildError::io(output_path, e))?;
+    let current_dir = std::env::current_dir().map_err(|e| BuildError::io(Path::new("."), e))?;
+    if current_dir.starts_with(&output_path) {
+        return Err(BuildError::Message(format!(
+            "Refusing to clean {} as it contains the current directory",
+            output_path.display()
+        )));
+    }
+    if CONFIG_FILES
+        .iter()
+        .any(|file_name| output_path.join(file_name).exists())
+    {
+        return Err(BuildError::Message(format!(
+            "Refusing to clean {} as it looks like an input folder",
+            output_path.display()
+        )));
+    }
+    let Some(outputs) = Manifest::read(&output_path) else {
+        return Err(BuildError::Message(format!(
+            "Refusing to clean {} as it has no {}, so it wasn't built by blog-builder",
+            output_path.display(),
+            MANIFEST_FILE_NAME
+        )));
     };
-    Ok(paths)
+
+    println!(
+        "Removing the generated files from {}",
+        output_path.display()
+    );
+    let removed_files = remove_outputs(&output_path, &outputs)?;
+    for file_name in [MANIFEST_FILE_NAME, CACHE_FILE_NAME] {
+        let path = output_path.join(file_name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| BuildError::io(&path, e))?;
+        }
+    }
+    let _ = fs::remove_dir(&output_path);
+    println!("Removed {} files", removed_files);
+    Ok(())
 }
 
-fn create_directory(path: &str) -> Result<(), Error> {
-    let path = Path::new(path);
+fn create_directory(path: &Path) -> Result<(), BuildError> {
     if !path.exists() {
-        fs::create_dir_all(path)?;
+        fs::create_dir_all(path).map_err(|e| BuildError::io(path, e))?;
     }
     Ok(())
 }
 
-fn build_images_folder(input_dir: &Path, output_dir: &Path) -> Result<(), Error> {
+fn build_images_folder(
+    input_dir: &Path,
+    output_dir: &Path,
+    cache: &mut BuildCache,
+    manifest: &mut Manifest,
+) -> Result<(), BuildError> {
     if input_dir.is_dir() {
         // find a path within this directory called images/
-        for entry in fs::read_dir(input_dir)? {
-            let entry = entry?;
+        for entry in fs::read_dir(input_dir).map_err(|e| BuildError::io(input_dir, e))? {
+            let entry = entry.map_err(|e| BuildError::io(input_dir, e))?;
             let path = entry.path();
-            if path.to_string_lossy().contains("images") {
-                if path.is_dir() {
-                    println!("Found images folder. Copying to destination...");
-                    let output_images_path = output_dir.join("images");
-                    copy_dir_to(&path, &output_images_path)?;
-                }
+            if path.to_string_lossy().contains("images") && path.is_dir() {
+                println!("Found images folder. Copying to destination...");
+                let output_images_path = output_dir.join("images");
+                copy_dir_to(&path, &output_images_path, cache, manifest)?;
             }
         }
     }
     Ok(())
 }
 
-fn build_style_folder(input_dir: &Path, output_dir: &Path) -> Result<(), Error> {
+fn build_style_folder(
+    input_dir: &Path,
+    output_dir: &Path,
+    cache: &mut BuildCache,
+    manifest: &mut Manifest,
+) -> Result<(), BuildError> {
     if input_dir.is_dir() {
         // find a path within this directory called style/
-        for entry in fs::read_dir(input_dir)? {
-            let entry = entry?;
+        for entry in fs::read_dir(input_dir).map_err(|e| BuildError::io(input_dir, e))? {
+            let entry = entry.map_err(|e| BuildError::io(input_dir, e))?;
             let path = entry.path();
-            if path.to_string_lossy().contains("style") {
-                if path.is_dir() {
-                    println!("Found style folder. Copying to destination...");
-                    let output_style_path = output_dir.join("style");
-                    copy_dir_to(&path, &output_style_path)?;
-                }
+            if path.to_string_lossy().contains("style") && path.is_dir() {
+                println!("Found style folder. Copying to destination...");
+                let output_style_path = output_dir.join("style");
+                copy_dir_to(&path, &output_style_path, cache, manifest)?;
             }
         }
     }
     Ok(())
 }
 
-fn build_content_folder(
+/// read_published_posts
+///
+/// Reads every post in the folder and keeps the ones that are published.
+fn read_published_posts(
+    input_dir: &Path,
+    folder_to_build: &str,
+    output_dir: &Path,
+    config: &SiteConfig,
+    layouts: &Layouts,
+    publish: &PublishArgs,
+    cache: &mut BuildCache,
+) -> Result<Vec<Post>, Vec<BuildError>> {
+    let posts = read_content_folder(
+        input_dir,
+        folder_to_build,
+        output_dir,
+        config,
+        layouts,
+        cache,
+    )?;
+
+    let today = Local::now().date_naive();
+    let (posts, unpublished_posts): (Vec<Post>, Vec<Post>) = posts
+        .into_iter()
+        .partition(|post| post.metadata.is_published(publish, today));
+    // Its page, and anything else a build that included it wrote, is removed once the build is done
+    for post in &unpublished_posts {
+        println!("Leaving out unpublished post {}", post.file_name);
+    }
+    Ok(posts)
+}
+
+/// write_posts
+///
+/// Writes the page of every post that wasn't reused from the last build.
+fn write_posts(output_dir: &Path, posts: &[Post]) -> Result<(), BuildError> {
+    for post in posts.iter().filter(|post| !post.from_cache) {
+        println!("Writing {} to {}", post.file_name, &output_dir.display());
+        let page_path = output_dir.join(&post.file_name);
+        create_directory(page_path.parent().unwrap_or(output_dir))?;
+        write_to_file(output_dir, &post.file_name, &post.content)?;
+    }
+    let unchanged_posts = posts.iter().filter(|post| post.from_cache).count();
+    if unchanged_posts > 0 {
+        println!("Skipped {} unchanged posts", unchanged_posts);
+    }
+    Ok(())
+}
+
+/// group_taxonomies
+///
+/// The terms of every taxonomy with their posts. Categories are the taxonomy every site has and always come first,
+/// the config can add more like tags.
+fn group_taxonomies(posts: &[Post], config: &SiteConfig) -> Vec<(Taxonomy, CategoryPosts)> {
+    std::iter::once(Taxonomy::categories())
+        .chain(config.taxonomies.iter().cloned())
+        .map(|taxonomy| {
+            let terms = group_by_term(posts, &taxonomy);
+            (taxonomy, terms)
+        })
+        .collect()
+}
+
+/// check_output_files
+///
+/// Works out the file every post, alias, listing, feed, page and redirect rule file is written to, and reports each file that
+/// two of them would share, such as a post called `rust.md` and the category Rust, naming both.
+/// Returns every file the build writes besides the copied images and style, for the manifest to list.
+fn check_output_files(
+    posts: &[Post],
+    taxonomies: &[(Taxonomy, CategoryPosts)],
+    config: &SiteConfig,
+) -> Result<Vec<String>, Vec<BuildError>> {
+    let mut files = OutputFiles::default();
+    files.claim("index.html", "the home page".to_string());
+    files.claim("all.html", "the list of all posts".to_string());
+    for format in &config.feeds {
+        files.claim(format.file_name(), "the feed of the whole site".to_string());
+    }
+
+    for post in posts {
+        files.claim(&post.file_name, format!("the post {}", post.source));
+        for alias in &post.metadata.aliases {
+            files.claim(
+                &alias_file_name(alias),
+                format!("the alias {} of {}", alias, post.source),
+            );
+        }
+    }
+
+    let rss = config.feeds.contains(&FeedFormat::Rss);
+    for (taxonomy, terms) in taxonomies {
+        files.claim(
+            &taxonomy.index_path(),
+            format!("the list of all {}", taxonomy.name),
+        );
+        for (term, _) in terms {
+            let source = if taxonomy.name == CATEGORIES {
+                format!("the category `{}`", term.name)
+            } else {
+                format!("the `{}` term `{}`", taxonomy.name, term.name)
+            };
+            if slugify(&term.name).is_empty() {
+                files.report(format!(
+                    "{} has no letters or numbers to make the name of its page from",
+                    source
+                ));
+                continue;
+            }
+            // A term whose page clashes has been reported already, its feed would only say the same again
+            let feed_source = format!("the feed of {}", source);
+            if files.claim(&term.path, source) && taxonomy.feeds && rss {
+                files.claim(&term.feed_path(), feed_source);
+            }
+        }
+    }
+
+    for section in &config.sections {
+        files.claim(
+            &section.listing_path(),
+            format!("the list of the {} section", section.folder),
+        );
+    }
+    for page in &config.pages {
+        files.claim(&page_file_name(page), format!("the page {}", page));
+    }
+    for rules in &config.redirect_rules {
+        files.claim(
+            rules.file_name(),
+            "the redirect rules for the host".to_string(),
+        );
+    }
+    files.finish()
+}
+
+/// read_content_folder
+///
+/// Reads and renders every post in the folder without writing anything, whether it is published or not.
+/// Posts are rendered in parallel, but always gathered in file name order so the output never depends on timing.
+/// A post with bad front matter doesn't stop the others from being read, so every problem can be reported at once.
+fn read_content_folder(
     input_dir: &Path,
     folder_to_build: &str,
     output_dir: &Path,
     config: &SiteConfig,
-) -> Result<(Vec<Post>, Vec<(Category, Vec<Post>)>), std::io::Error> {
+    layouts: &Layouts,
+    cache: &mut BuildCache,
+) -> Result<Vec<Post>, Vec<BuildError>> {
     let mut posts: Vec<Post> = Vec::new();
-    let mut categories: Vec<(Category, Vec<Post>)> = Vec::new();
+    let mut errors: Vec<BuildError> = Vec::new();
 
     let path_to_build = Path::new(input_dir).join(folder_to_build);
-    if path_to_build.is_dir() {
-        for entry in fs::read_dir(&path_to_build)? {
-            let entry = entry?;
-            let path = entry.path();
+    if !path_to_build.is_dir() {
+        return Ok(posts);
+    }
 
-            if let Some(file_contents) = read_file(&path).ok() {
-                let (file_metadata, file_contents) =
-                    MetaData::read_metadata_and_contents(&file_contents);
-
-                let mut post_html = markdown::to_html(&file_contents);
-                post_html = add_date_to_body(&post_html, &file_metadata.date);
-                let html_body = add_title_to_body(&post_html, &file_metadata.title);
-                let wrapped_html = wrap_in_header_and_footer(&input_dir, &html_body, 0)?;
-                let wrapped_html_with_head = add_head(&wrapped_html, &file_metadata.title, false)?;
-                let html_file_name = create_html_file_name(&path.to_str().unwrap()).unwrap();
-                fs::create_dir_all(&output_dir)?;
-                println!("Writing {} to {}", html_file_name, &output_dir.display());
-                write_to_file(&output_dir, &html_file_name, &wrapped_html_with_head)?;
-
-                let link_path = format!("./{}", html_file_name);
-
-                let public_link =
-                    format!("{}/{}{}", config.url, output_dir.display(), html_file_name);
-
-                let post = Post {
-                    metadata: file_metadata.clone(),
-                    content: wrapped_html_with_head,
-                    path: link_path,
-                    public_link: public_link,
-                };
-
-                posts.push(post.clone());
-
-                // Process Categories
-                for category in &file_metadata.categories {
-                    let category_path = get_category_path(&category);
-                    let category = Category {
-                        name: category.clone(),
-                        path: category_path.clone(),
-                    };
-
-                    let mut found = false;
-                    for (cat, posts) in &mut categories {
-                        if cat.name == category.name {
-                            found = true;
-                            posts.push(post.clone());
-                        }
-                    }
-                    if !found {
-                        categories.push((category, vec![post.clone()]));
-                    }
-                }
+    // Without the header and footer no post can be built, so stop here rather than once per post
+    wrap_in_header_and_footer(input_dir, "")?;
+    if let Some(layout) = config
+        .section(folder_to_build)
+        .and_then(|section| section.layout.as_deref())
+    {
+        layouts.require(layout)?;
+    }
+
+    let paths = find_markdown_files(&path_to_build)?;
+
+    let cache_ref: &BuildCache = cache;
+    let results: Vec<_> = paths
+        .par_iter()
+        .map(|path| {
+            let file_contents = read_file(path)?;
+            let source_key = path.strip_prefix(input_dir).unwrap_or(path);
+            let source_key = source_key.to_string_lossy().into_owned();
+            let source_hash = hash_bytes(file_contents.as_bytes());
+            let rendered = cache_ref.rendered(&source_key, source_hash).cloned();
+
+            let post = read_post(
+                input_dir,
+                path,
+                &file_contents,
+                output_dir,
+                config,
+                layouts,
+                rendered,
+            );
+            Ok((source_key, source_hash, post))
+        })
+        .collect();
+
+    for result in results {
+        let (source_key, source_hash, post) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                errors.push(error);
+                continue;
             }
-        }
+        };
+        cache.record(&source_key, source_hash);
+        let post = match post {
+            Ok(post) => post,
+            Err(post_errors) => {
+                errors.extend(post_errors);
+                continue;
+            }
+        };
+        cache.record_rendered(
+            &source_key,
+            RenderedPost {
+                body: post.body.clone(),
+                summary: post.metadata.summary.clone(),
+            },
+        );
+
+        posts.push(post);
     }
 
-    Ok((posts, categories))
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(posts)
+}
+
+/// read_post
+///
+/// Parses a single post and renders it into a full page.
+/// When the post hasn't changed since the last build, what it rendered to then and the page already in the
+/// output directory are reused instead.
+fn read_post(
+    input_dir: &Path,
+    path: &Path,
+    file_contents: &str,
+    output_dir: &Path,
+    config: &SiteConfig,
+    layouts: &Layouts,
+    rendered: Option<RenderedPost>,
+) -> Result<Post, Vec<BuildError>> {
+    let (mut file_metadata, file_contents) =
+        MetaData::read_metadata_and_contents(path, file_contents, &config.taxonomies)?;
+    let source_path = path.strip_prefix(input_dir).unwrap_or(path);
+    // Posts in sub folders keep their folder, so `posts/rust/intro.md` is in the `rust` folder
+    let folder: PathBuf = source_path
+        .parent()
+        .map(|folder| folder.components().skip(1).collect())
+        .unwrap_or_default();
+    let section = source_path
+        .components()
+        .next()
+        .and_then(|top| config.section(&top.as_os_str().to_string_lossy()));
+    let slug = match &file_metadata.slug {
+        Some(slug) => slug.clone(),
+        None => slugify(&path.file_stem().unwrap().to_string_lossy()),
+    };
+    if slug.is_empty() {
+        return Err(BuildError::Message(format!(
+            "{}: there is nothing in the file name to make a slug from, give the post a `slug`",
+            path.display()
+        ))
+        .into());
+    }
+    let mut url = expand_permalink(
+        &config.permalink,
+        &file_metadata.date,
+        &slug,
+        &folder.to_string_lossy().replace('\\', "/"),
+    );
+    // A section's posts are written inside its own folder
+    if let Some(section) = section {
+        url = format!("{}/{}", section.folder, url);
+    }
+    let html_file_name = permalink_file_name(&url);
+    let levels_down = html_file_name.matches('/').count();
+
+    let cached_page = if rendered.is_some() {
+        fs::read_to_string(output_dir.join(&html_file_name)).ok()
+    } else {
+        None
+    };
+    let from_cache = cached_page.is_some();
+    let body = match rendered {
+        Some(rendered) => {
+            file_metadata.summary = rendered.summary;
+            rendered.body
+        }
+        None => {
+            let (markdown_before_more, body) = match file_contents.split_once(MORE_MARKER) {
+                Some((before, after)) => (
+                    Some(before),
+                    markdown::to_html(&format!("{}{}", before, after)),
+                ),
+                None => (None, markdown::to_html(file_contents)),
+            };
+            if file_metadata.summary.is_empty() {
+                file_metadata.summary =
+                    create_excerpt(markdown_before_more, &body, config.excerpt_words);
+            }
+            body
+        }
+    };
+
+    let link_path = format!("./{}", url);
+
+    let public_link = config.public_url(&url);
+
+    let mut post = Post {
+        metadata: file_metadata,
+        content: String::new(),
+        reading_time: reading_time(&body),
+        body,
+        path: link_path,
+        public_link,
+        file_name: html_file_name,
+        source: source_path.to_string_lossy().replace('\\', "/"),
+        in_feeds: section.is_none_or(|section| section.feeds),
+        from_cache,
+    };
+    post.content = match cached_page {
+        Some(page) => page,
+        None => {
+            let post_layout = section
+                .and_then(|section| section.layout.as_deref())
+                .unwrap_or(POST_LAYOUT);
+            let article = if layouts.has(post_layout) {
+                layouts.render(
+                    post_layout,
+                    context! {
+                        site => site_context(config),
+                        post => post_context(&post),
+                        content => Value::from_safe_string(post.body.clone()),
+                    },
+                )?
+            } else {
+                let post_html = add_date_to_body(&post.body, &post.metadata.date);
+                add_title_to_body(&post_html, &post.metadata.title)
+            };
+            layouts.render_page(
+                input_dir,
+                config,
+                &post.metadata.title,
+                "",
+                &article,
+                levels_down,
+            )?
+        }
+    };
+    Ok(post)
 }
 
 fn build_main_page(
     input_dir: &Path,
     output_dir: &Path,
-    posts: &Vec<Post>,
-    site_title: &str,
-) -> Result<(), Error> {
+    posts: &[Post],
+    categories: &CategoryPosts,
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), BuildError> {
     if input_dir.is_dir() {
         // find a path within this directory called index.md
-        for entry in fs::read_dir(input_dir)? {
-            let entry = entry?;
+        for entry in fs::read_dir(input_dir).map_err(|e| BuildError::io(input_dir, e))? {
+            let entry = entry.map_err(|e| BuildError::io(input_dir, e))?;
             let path = entry.path();
-            if path.to_string_lossy().contains("index.html") {
-                if path.is_file() {
-                    println!("Found main template. Building and copying to destination...");
-                    let index_template = get_index_template(input_dir)?;
-                    let index_content = add_recent_posts(&index_template, &posts, 10);
-                    let wrapped_index = wrap_in_header_and_footer(&input_dir, &index_content, 0)?;
-                    let wrapped_index_with_head = add_head(&wrapped_index, site_title, false)?;
-                    write_to_file(output_dir, "index.html", &wrapped_index_with_head)?;
+            if path.to_string_lossy().contains("index.html") && path.is_file() {
+                println!("Found main template. Building and copying to destination...");
+                let index_template = get_index_template(input_dir)?;
+                let with_previews = config.previews.contains(&Listing::Index);
+                let mut index_content =
+                    fill_index_template(&path, &index_template, posts, categories, with_previews)?;
+                if layouts.has(INDEX_LAYOUT) {
+                    index_content = layouts.render(
+                        INDEX_LAYOUT,
+                        context! {
+                            site => site_context(config),
+                            posts => posts_context(posts),
+                            content => Value::from_safe_string(index_content),
+                        },
+                    )?;
                 }
+                let wrapped_index_with_head =
+                    layouts.render_page(input_dir, config, &config.title, "", &index_content, 0)?;
+                write_to_file(output_dir, "index.html", &wrapped_index_with_head)?;
             }
         }
     }
@@ -226,50 +835,214 @@ fn build_main_page(
 fn build_all_posts_page(
     input_dir: &Path,
     output_dir: &Path,
-    posts: &Vec<Post>,
-    site_title: &str,
-) -> Result<(), Error> {
+    posts: &[Post],
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), BuildError> {
     if input_dir.is_dir() {
-        let content = group_by_year_as_html(&posts);
-        let wrapped_index = wrap_in_header_and_footer(&input_dir, &content, 0)?;
-        let wrapped_index_with_head = add_head(&wrapped_index, site_title, false)?;
+        let with_previews = config.previews.contains(&Listing::AllPosts);
+        let mut content = group_by_year_as_html(posts, with_previews);
+        if layouts.has(LIST_LAYOUT) {
+            content = layouts.render(
+                LIST_LAYOUT,
+                context! {
+                    site => site_context(config),
+                    posts => posts_context(posts),
+                    content => Value::from_safe_string(content),
+                },
+            )?;
+        }
+        let wrapped_index_with_head =
+            layouts.render_page(input_dir, config, &config.title, "", &content, 0)?;
         write_to_file(output_dir, "all.html", &wrapped_index_with_head)?;
     }
     Ok(())
 }
 
-fn build_categories_index_page(
+/// build_section_page
+///
+/// Writes the page listing a section's posts, grouped by year like the all posts page.
+fn build_section_page(
     input_dir: &Path,
     output_dir: &Path,
-    categories: &Vec<(Category, Vec<Post>)>,
-    site_title: &str,
-) -> Result<(), Error> {
+    section: &Section,
+    posts: &[Post],
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), BuildError> {
+    let with_previews = config.previews.contains(&Listing::AllPosts);
+    let mut content = format!(
+        "<h2>{}</h2>\n{}",
+        section.title,
+        group_by_year_as_html(posts, with_previews)
+    );
+    if layouts.has(LIST_LAYOUT) {
+        content = layouts.render(
+            LIST_LAYOUT,
+            context! {
+                site => site_context(config),
+                section => section_context(section),
+                posts => posts_context(posts),
+                content => Value::from_safe_string(content),
+            },
+        )?;
+    }
+    let page = layouts.render_page(input_dir, config, &section.title, "", &content, 0)?;
+    write_to_file(output_dir, &section.listing_path(), &page)?;
+    Ok(())
+}
+
+/// build_pages
+///
+/// Writes each standalone page listed in the config, like an About page, next to the home page.
+fn build_pages(
+    input_dir: &Path,
+    output_dir: &Path,
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), Vec<BuildError>> {
+    let mut errors = Vec::new();
+    for page in &config.pages {
+        match read_page(input_dir, page, config, layouts) {
+            Ok((file_name, html)) => {
+                let page_path = output_dir.join(&file_name);
+                create_directory(page_path.parent().unwrap_or(output_dir))?;
+                write_to_file(output_dir, &file_name, &html)?;
+            }
+            Err(page_errors) => errors.extend(page_errors),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// page_file_name
+///
+/// Where a standalone page is written, `about.md` becomes `about.html`.
+fn page_file_name(page: &str) -> String {
+    format!("{}.html", page.trim_end_matches(".md"))
+}
+
+/// read_page
+///
+/// Renders a standalone page from its markdown file, returning where it is written along with the page.
+/// Pages get the header and footer like posts, but no date, and are never listed with the posts.
+fn read_page(
+    input_dir: &Path,
+    page: &str,
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(String, String), Vec<BuildError>> {
+    let path = input_dir.join(page);
+    let file_contents = read_file(&path)?;
+    let (metadata, markdown) = MetaData::read_metadata_and_contents(&path, &file_contents, &[])?;
+    let body = markdown::to_html(&markdown.replace(MORE_MARKER, ""));
+
+    let file_name = page_file_name(page);
+    let levels_down = file_name.matches('/').count();
+    let article = if layouts.has(PAGE_LAYOUT) {
+        layouts.render(
+            PAGE_LAYOUT,
+            context! {
+                site => site_context(config),
+                page => context! { title => metadata.title.clone() },
+                content => Value::from_safe_string(body),
+            },
+        )?
+    } else {
+        add_title_to_body(&body, &metadata.title)
+    };
+    let html = layouts.render_page(
+        input_dir,
+        config,
+        &metadata.title,
+        "",
+        &article,
+        levels_down,
+    )?;
+    Ok((file_name, html))
+}
+
+/// build_taxonomy_index_page
+///
+/// Writes the page listing every term of a taxonomy, like `categories.html`, with the posts of each.
+fn build_taxonomy_index_page(
+    input_dir: &Path,
+    output_dir: &Path,
+    taxonomy: &Taxonomy,
+    terms: &CategoryPosts,
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), BuildError> {
     if input_dir.is_dir() {
-        let mut content = String::from("<h2>Categories</h2>\n<ul>\n");
+        let mut content = format!("<h2>{}</h2>\n<ul>\n", taxonomy.title);
 
-        for (category, posts) in categories {
-            let category_list = create_category_list_html(category, posts);
-            content.push_str(&category_list);
+        for (term, posts) in terms {
+            let term_list = create_category_list_html(term, posts, false);
+            content.push_str(&term_list);
         }
-        let wrapped_index = wrap_in_header_and_footer(&input_dir, &content, 0)?;
-        let wrapped_index_with_head = add_head(&wrapped_index, site_title, false)?;
-        write_to_file(output_dir, "categories.html", &wrapped_index_with_head)?;
+        let wrapped_index_with_head =
+            layouts.render_page(input_dir, config, &config.title, "", &content, 0)?;
+        write_to_file(output_dir, &taxonomy.index_path(), &wrapped_index_with_head)?;
     }
     Ok(())
 }
 
-fn build_category_pages(
+/// build_term_pages
+///
+/// Writes a page listing the posts of each term of a taxonomy, such as each category.
+/// When RSS is enabled and the taxonomy has feeds, each term also gets its own feed, linked from the head of its page.
+fn build_term_pages(
     input_dir: &Path,
     output_dir: &Path,
-    categories: &Vec<(Category, Vec<Post>)>,
-    site_title: &str,
-) -> Result<(), Error> {
+    taxonomy: &Taxonomy,
+    terms: &CategoryPosts,
+    config: &SiteConfig,
+    layouts: &Layouts,
+) -> Result<(), BuildError> {
+    let with_feeds = taxonomy.feeds && config.feeds.contains(&FeedFormat::Rss);
     if input_dir.is_dir() {
-        for (category, posts) in categories {
-            let content = &create_category_list_html(category, posts);
-            let wrapped_index = wrap_in_header_and_footer(&input_dir, &content, 0)?;
-            let wrapped_index_with_head = add_head(&wrapped_index, site_title, false)?;
-            write_to_file(output_dir, &category.path, &wrapped_index_with_head)?;
+        for (term, posts) in terms {
+            let page_path = output_dir.join(&term.path);
+            create_directory(page_path.parent().unwrap_or(output_dir))?;
+            let levels_down = term.path.matches('/').count();
+
+            let mut head_links = String::new();
+            if with_feeds {
+                build_category_rss_feed(output_dir, term, posts, config)?;
+                head_links = format!(
+                    "<link rel=\"alternate\" type=\"application/rss+xml\" title=\"{} - {}\" href=\"./{}\">\n",
+                    config.title.replace('"', "&quot;"),
+                    term.name.replace('"', "&quot;"),
+                    term.feed_path()
+                );
+            }
+            let with_previews = config.previews.contains(&Listing::Categories);
+            let mut content = create_category_list_html(term, posts, with_previews);
+            if layouts.has(CATEGORY_LAYOUT) {
+                content = layouts.render(
+                    CATEGORY_LAYOUT,
+                    context! {
+                        site => site_context(config),
+                        taxonomy => taxonomy_context(taxonomy),
+                        category => category_context(term),
+                        posts => posts_context(posts),
+                        content => Value::from_safe_string(content),
+                    },
+                )?;
+            }
+            let wrapped_index_with_head = layouts.render_page(
+                input_dir,
+                config,
+                &config.title,
+                &head_links,
+                &content,
+                levels_down,
+            )?;
+            write_to_file(output_dir, &term.path, &wrapped_index_with_head)?;
         }
     }
     Ok(())
@@ -0,0 +1,88 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::BuildError,
+    files::{remove_outputs, write_to_file},
+};
+
+/// The manifest lives in the output directory, next to the files it lists.
+pub const MANIFEST_FILE_NAME: &str = ".blog-builder-manifest";
+
+/// Manifest
+///
+/// Lists every file a build wrote into the output directory, relative to it, so `clean` only ever
+/// removes what was generated and the next build knows which of those files it no longer writes.
+pub struct Manifest {
+    output_dir: PathBuf,
+    previous: BTreeSet<String>,
+    current: BTreeSet<String>,
+}
+
+impl Manifest {
+    /// load
+    ///
+    /// Reads the manifest left in the output directory by the previous build, if there is one.
+    pub fn load(output_dir: &Path) -> Manifest {
+        Manifest {
+            output_dir: output_dir.to_path_buf(),
+            previous: Manifest::read(output_dir).unwrap_or_default(),
+            current: BTreeSet::new(),
+        }
+    }
+
+    /// read
+    ///
+    /// The files the last build wrote into the output directory.
+    /// Returns `None` when there is no manifest, as the folder wasn't built by this tool.
+    pub fn read(output_dir: &Path) -> Option<BTreeSet<String>> {
+        let file_contents = fs::read_to_string(output_dir.join(MANIFEST_FILE_NAME)).ok()?;
+        Some(file_contents.lines().map(str::to_string).collect())
+    }
+
+    /// record
+    ///
+    /// Remembers that this build wrote a file, given relative to the output directory.
+    pub fn record(&mut self, file_name: &str) {
+        self.current.insert(file_name.replace('\\', "/"));
+    }
+
+    /// record_path
+    ///
+    /// Remembers that this build wrote the file at `path`, somewhere inside the output directory.
+    pub fn record_path(&mut self, path: &Path) {
+        let relative = path.strip_prefix(&self.output_dir).unwrap_or(path);
+        self.record(&relative.to_string_lossy());
+    }
+
+    /// remove_stale_files
+    ///
+    /// Removes every file the previous build wrote that this one didn't, like the pages and feeds of a
+    /// category whose only post is now a draft, so nothing that was taken out of the site stays live.
+    pub fn remove_stale_files(&self) -> Result<(), BuildError> {
+        let removed_files =
+            remove_outputs(&self.output_dir, self.previous.difference(&self.current))?;
+        if removed_files > 0 {
+            println!(
+                "Removed {} files left over from the last build",
+                removed_files
+            );
+        }
+        Ok(())
+    }
+
+    /// save
+    ///
+    /// Writes the files recorded during this build into the manifest.
+    pub fn save(&self) -> Result<(), BuildError> {
+        let mut contents = String::new();
+        for file_name in &self.current {
+            contents.push_str(file_name);
+            contents.push('\n');
+        }
+        write_to_file(&self.output_dir, MANIFEST_FILE_NAME, &contents)
+    }
+}
@@ -1,4 +1,14 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use yaml_rust::Yaml;
+
+use crate::{
+    argparse::PublishArgs,
+    config::{Taxonomy, CATEGORIES},
+    error::BuildError,
+    slug::is_safe_slug,
+};
 
 #[derive(Debug, Clone)]
 pub struct MetaData {
@@ -6,53 +16,392 @@ pub struct MetaData {
     pub date: NaiveDate,
     pub categories: Vec<String>,
     pub summary: String,
+    /// An image shown with the post's preview on listing pages.
+    pub cover: Option<String>,
+    /// Replaces the file name in the post's URL.
+    pub slug: Option<String>,
+    /// Old paths of the post within the site, each of which gets a page redirecting to it.
+    pub aliases: Vec<String>,
+    /// Unfinished, so left out of the site unless drafts are asked for.
+    pub draft: bool,
+    /// The day the post is taken down, left out of the site from then on.
+    pub expires: Option<NaiveDate>,
+    /// The terms of each taxonomy in the config besides categories, keyed by the taxonomy's name.
+    pub terms: BTreeMap<String, Vec<String>>,
 }
 
 impl MetaData {
     /// read_metadata_and_contents
     ///
-    /// Takes a string slice of the file contents and returns a tuple of the metadata and the content.
-    /// It reads the metadata block from the top of the markdown files and remove them afterwards
-    pub fn read_metadata_and_contents(file_contents: &str) -> (MetaData, &str) {
-        let (yaml, content) = frontmatter::parse_and_find_content(file_contents).unwrap();
+    /// Takes the path of a post and a string slice of its contents and returns a tuple of the metadata and the content.
+    /// It reads the metadata block from the top of the markdown files and remove them afterwards.
+    /// Every problem found in the metadata block is returned, pointing at the line and column it was found on.
+    /// Besides the categories, the terms of each of the given taxonomies are read from the key named after it.
+    pub fn read_metadata_and_contents<'a>(
+        path: &Path,
+        file_contents: &'a str,
+        taxonomies: &[Taxonomy],
+    ) -> Result<(MetaData, &'a str), Vec<BuildError>> {
+        let (yaml, content) = frontmatter::parse_and_find_content(file_contents).map_err(|e| {
+            // The scanner reports its position within the front matter block as part of the message,
+            // so drop that and point into the post instead. The block starts after the opening "---".
+            let message = e.to_string();
+            let message = match message.rfind(" at line ") {
+                Some(position) => message[..position].to_string(),
+                None => message,
+            };
+            BuildError::FrontMatter {
+                path: path.to_path_buf(),
+                line: e.marker().line() + 1,
+                column: e.marker().col() + 1,
+                message,
+            }
+        })?;
 
         let mut metadata = MetaData {
             title: String::from(""),
             date: NaiveDate::from_ymd_opt(1970, 1, 1).unwrap(),
             categories: Vec::new(),
             summary: String::from(""),
+            cover: None,
+            slug: None,
+            aliases: Vec::new(),
+            draft: false,
+            expires: None,
+            terms: BTreeMap::new(),
+        };
+        let mut errors = Vec::new();
+        let field_error = |key: &str, message: String| {
+            let (line, column) = find_key_position(file_contents, key);
+            BuildError::FrontMatter {
+                path: path.to_path_buf(),
+                line,
+                column,
+                message,
+            }
         };
 
         if let Some(yaml_hash) = yaml {
             if let Some(yaml) = yaml_hash.as_hash() {
-                if let Some(title) = yaml.get(&yaml_rust::Yaml::from_str("title")) {
-                    metadata.title = title.as_str().unwrap().to_string();
-                }
-                if let Some(date) = yaml.get(&yaml_rust::Yaml::from_str("date")) {
-                    metadata.date =
-                        NaiveDate::parse_from_str(date.as_str().unwrap(), "%Y-%m-%d").unwrap();
-                }
-                if let Some(categories) = yaml.get(&yaml_rust::Yaml::from_str("categories")) {
-                    if let Some(categories) = categories.as_vec() {
-                        for category in categories {
-                            metadata
-                                .categories
-                                .push(category.as_str().unwrap().to_string());
+                if let Some(title) = yaml.get(&Yaml::from_str("title")) {
+                    match title.as_str() {
+                        Some(title) => metadata.title = title.to_string(),
+                        None => errors.push(field_error(
+                            "title",
+                            String::from("`title` should be a piece of text"),
+                        )),
+                    }
+                }
+                if let Some(date) = yaml.get(&Yaml::from_str("date")) {
+                    match date
+                        .as_str()
+                        .map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+                    {
+                        Some(Ok(date)) => metadata.date = date,
+                        _ => errors.push(field_error(
+                            "date",
+                            format!(
+                                "`date` should be a date like 2023-08-01, found {}",
+                                describe_yaml(date)
+                            ),
+                        )),
+                    }
+                }
+                if let Some(summary) = yaml.get(&Yaml::from_str("summary")) {
+                    match summary {
+                        Yaml::String(summary) => metadata.summary = summary.trim().to_string(),
+                        Yaml::Null => {}
+                        _ => errors.push(field_error(
+                            "summary",
+                            format!(
+                                "`summary` should be a piece of text, found {}",
+                                describe_yaml(summary)
+                            ),
+                        )),
+                    }
+                }
+                if let Some(cover) = yaml.get(&Yaml::from_str("cover")) {
+                    match cover {
+                        Yaml::String(cover) => metadata.cover = Some(cover.clone()),
+                        Yaml::Null => {}
+                        _ => errors.push(field_error(
+                            "cover",
+                            format!(
+                                "`cover` should be the path to an image, found {}",
+                                describe_yaml(cover)
+                            ),
+                        )),
+                    }
+                }
+                if let Some(slug) = yaml.get(&Yaml::from_str("slug")) {
+                    match slug.as_str() {
+                        Some(slug) if is_safe_slug(slug) => metadata.slug = Some(slug.to_string()),
+                        _ => errors.push(field_error(
+                            "slug",
+                            format!(
+                                "`slug` should only use letters, numbers, -, _, . and ~, and can't be . or .., found {}",
+                                describe_yaml(slug)
+                            ),
+                        )),
+                    }
+                }
+                if let Some(draft) = yaml.get(&Yaml::from_str("draft")) {
+                    match draft {
+                        Yaml::Boolean(draft) => metadata.draft = *draft,
+                        Yaml::Null => {}
+                        _ => errors.push(field_error(
+                            "draft",
+                            format!(
+                                "`draft` should be true or false, found {}",
+                                describe_yaml(draft)
+                            ),
+                        )),
+                    }
+                }
+                if let Some(expires) = yaml.get(&Yaml::from_str("expires")) {
+                    match expires {
+                        Yaml::Null => {}
+                        _ => match expires
+                            .as_str()
+                            .map(|expires| NaiveDate::parse_from_str(expires, "%Y-%m-%d"))
+                        {
+                            Some(Ok(expires)) => metadata.expires = Some(expires),
+                            _ => errors.push(field_error(
+                                "expires",
+                                format!(
+                                    "`expires` should be a date like 2023-08-01, found {}",
+                                    describe_yaml(expires)
+                                ),
+                            )),
+                        },
+                    }
+                }
+                if let Some(aliases) = yaml.get(&Yaml::from_str("aliases")) {
+                    match aliases {
+                        Yaml::Array(aliases) => {
+                            for alias in aliases {
+                                match alias.as_str() {
+                                    Some(alias)
+                                        if !alias.trim_matches('/').is_empty()
+                                            && !alias.split('/').any(|part| part == "..") =>
+                                    {
+                                        metadata.aliases.push(alias.to_string())
+                                    }
+                                    _ => errors.push(field_error(
+                                        "aliases",
+                                        format!(
+                                            "each alias should be a path within the site, found {}",
+                                            describe_yaml(alias)
+                                        ),
+                                    )),
+                                }
+                            }
+                        }
+                        Yaml::Null => {}
+                        _ => errors.push(field_error(
+                            "aliases",
+                            String::from("`aliases` should be a list"),
+                        )),
+                    }
+                }
+                // Categories and every other taxonomy are lists of terms, read the same way
+                let mut read_terms = |key: &str, describe_term: &str| {
+                    let mut terms = Vec::new();
+                    match yaml.get(&Yaml::from_str(key)) {
+                        Some(Yaml::Array(entries)) => {
+                            for term in entries {
+                                match term.as_str() {
+                                    Some(term) => terms.push(term.to_string()),
+                                    None => errors.push(field_error(
+                                        key,
+                                        format!(
+                                            "{} should be a piece of text, found {}",
+                                            describe_term,
+                                            describe_yaml(term)
+                                        ),
+                                    )),
+                                }
+                            }
+                        }
+                        Some(Yaml::Null) | None => {}
+                        Some(_) => {
+                            errors.push(field_error(key, format!("`{}` should be a list", key)))
                         }
                     }
+                    terms
+                };
+                metadata.categories = read_terms("categories", "each category");
+                for taxonomy in taxonomies {
+                    let terms = read_terms(
+                        &taxonomy.name,
+                        &format!("each entry in `{}`", taxonomy.name),
+                    );
+                    metadata.terms.insert(taxonomy.name.clone(), terms);
                 }
             }
         }
 
-        (metadata, content)
+        if errors.is_empty() {
+            Ok((metadata, content))
+        } else {
+            Err(errors)
+        }
     }
 
-    pub fn rss_formatted_date(&self) -> String {
-        // Assuming time as 00:00:00
-        let datetime =
-            NaiveDateTime::new(self.date, chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+    /// terms
+    ///
+    /// The post's terms in a taxonomy, which for the built in one are its categories.
+    pub fn terms(&self, taxonomy: &str) -> &[String] {
+        if taxonomy == CATEGORIES {
+            &self.categories
+        } else {
+            self.terms.get(taxonomy).map_or(&[], Vec::as_slice)
+        }
+    }
 
+    /// is_published
+    ///
+    /// Whether the post belongs on the site built on `today`: it isn't a draft, its date has come and it hasn't expired.
+    /// Each of those can be let through by the matching flag in `publish`.
+    pub fn is_published(&self, publish: &PublishArgs, today: NaiveDate) -> bool {
+        (!self.draft || publish.drafts)
+            && (self.date <= today || publish.future)
+            && (self.expires.is_none_or(|expires| expires > today) || publish.expired)
+    }
+
+    /// published_at
+    ///
+    /// Posts only have a date, so they are treated as published at midnight UTC.
+    pub fn published_at(&self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.date.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    pub fn rss_formatted_date(&self) -> String {
         // Format to RFC 822 style
-        datetime.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+        self.published_at()
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string()
+    }
+}
+
+/// find_key_position
+///
+/// Finds the 1-based line and column of the value for a top level key in the front matter block.
+/// YAML values don't remember where they were parsed from, so this looks for the key by hand.
+/// Falls back to the first line when the key can't be found.
+fn find_key_position(file_contents: &str, key: &str) -> (usize, usize) {
+    let prefix = format!("{}:", key);
+    for (i, line) in file_contents.lines().enumerate().skip(1) {
+        if line.trim_end() == "---" {
+            break;
+        }
+        if let Some(rest) = line.strip_prefix(&prefix) {
+            let value_offset = rest.len() - rest.trim_start().len();
+            return (i + 1, prefix.len() + value_offset + 1);
+        }
+    }
+    (1, 1)
+}
+
+fn describe_yaml(value: &Yaml) -> String {
+    match value {
+        Yaml::String(text) => format!("\"{}\"", text),
+        Yaml::Integer(number) => number.to_string(),
+        Yaml::Real(number) => number.clone(),
+        Yaml::Boolean(boolean) => boolean.to_string(),
+        Yaml::Array(_) => String::from("a list"),
+        Yaml::Hash(_) => String::from("a mapping"),
+        _ => String::from("nothing"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The metadata of a post with the given front matter besides its title.
+    fn metadata(front_matter: &str) -> MetaData {
+        let post = format!("---\ntitle: Post\n{}---\n", front_matter);
+        MetaData::read_metadata_and_contents(Path::new("post.md"), &post, &[])
+            .unwrap()
+            .0
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()
+    }
+
+    fn publish(drafts: bool, future: bool, expired: bool) -> PublishArgs {
+        PublishArgs {
+            drafts,
+            future,
+            expired,
+        }
+    }
+
+    const POST: &str = "---\ntitle: \"Hello\"\ndate:   2023-08-01\ncategories:\n  - Rust\n---\n\ndate: not front matter\n";
+
+    #[test]
+    fn key_positions_point_at_the_value() {
+        assert_eq!(find_key_position(POST, "title"), (2, 8));
+        assert_eq!(find_key_position(POST, "date"), (3, 9));
+        assert_eq!(find_key_position(POST, "categories"), (4, 12));
+    }
+
+    #[test]
+    fn keys_after_the_front_matter_are_not_found() {
+        assert_eq!(find_key_position(POST, "summary"), (1, 1));
+        let post = "---\ntitle: x\n---\nslug: after\n";
+        assert_eq!(find_key_position(post, "slug"), (1, 1));
+    }
+
+    #[test]
+    fn only_whole_top_level_keys_are_found() {
+        let post = "---\nsubtitle: x\n  title: nested\ntitle: top\n---\n";
+        assert_eq!(find_key_position(post, "title"), (4, 8));
+    }
+
+    #[test]
+    fn windows_line_endings_are_handled() {
+        let post = "---\r\ntitle: x\r\n---\r\nslug: after\r\n";
+        assert_eq!(find_key_position(post, "title"), (2, 8));
+        assert_eq!(find_key_position(post, "slug"), (1, 1));
+    }
+
+    #[test]
+    fn dated_posts_are_published() {
+        let post = metadata("date: 2024-06-01\n");
+        assert!(post.is_published(&PublishArgs::default(), today()));
+    }
+
+    #[test]
+    fn drafts_are_only_published_when_asked_for() {
+        let post = metadata("date: 2024-05-01\ndraft: true\n");
+        assert!(!post.is_published(&PublishArgs::default(), today()));
+        assert!(post.is_published(&publish(true, false, false), today()));
+    }
+
+    #[test]
+    fn future_posts_are_only_published_when_asked_for() {
+        let post = metadata("date: 2024-06-02\n");
+        assert!(!post.is_published(&PublishArgs::default(), today()));
+        assert!(post.is_published(&publish(false, true, false), today()));
+    }
+
+    #[test]
+    fn posts_are_taken_down_from_their_expiry_date() {
+        let post = metadata("date: 2024-05-01\nexpires: 2024-06-02\n");
+        assert!(post.is_published(&PublishArgs::default(), today()));
+        let post = metadata("date: 2024-05-01\nexpires: 2024-06-01\n");
+        assert!(!post.is_published(&PublishArgs::default(), today()));
+        assert!(post.is_published(&publish(false, false, true), today()));
+    }
+
+    #[test]
+    fn each_flag_only_lets_its_own_posts_through() {
+        let post = metadata("date: 2024-07-01\ndraft: true\nexpires: 2024-06-01\n");
+        assert!(!post.is_published(&publish(true, false, false), today()));
+        assert!(!post.is_published(&publish(false, true, true), today()));
+        assert!(post.is_published(&publish(true, true, true), today()));
     }
 }
@@ -0,0 +1,136 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::urls::normalise_path;
+
+/// Where posts go unless the config says otherwise: named after their file, in the same folders as in `posts`.
+pub const DEFAULT_PERMALINK: &str = "/:folder/:slug.html";
+
+/// The placeholders a permalink pattern can use.
+const PLACEHOLDERS: [&str; 5] = [":year", ":month", ":day", ":slug", ":folder"];
+
+/// validate_permalink
+///
+/// Checks a permalink pattern from the config. It has to include the slug so that no two posts share
+/// a URL, only use known placeholders, and end in either `/` for a folder or `.html` for a page.
+/// Folders named `.` or `..` aren't allowed, every post stays inside the site.
+pub fn validate_permalink(pattern: &str) -> Result<(), String> {
+    for segment in pattern.split('/') {
+        if segment == "." || segment == ".." {
+            return Err(String::from("`permalink` can't use . or .. as a folder"));
+        }
+        let mut rest = segment;
+        while let Some(start) = rest.find(':') {
+            let placeholder = &rest[start..];
+            match PLACEHOLDERS.iter().find(|p| placeholder.starts_with(*p)) {
+                Some(known) => rest = &placeholder[known.len()..],
+                None => {
+                    return Err(format!(
+                        "unknown placeholder in `permalink`, expected any of: {}",
+                        PLACEHOLDERS.join(", ")
+                    ))
+                }
+            }
+        }
+    }
+    if !pattern.contains(":slug") {
+        return Err(String::from("`permalink` needs to include :slug"));
+    }
+    if !pattern.ends_with('/') && !pattern.ends_with(".html") {
+        return Err(String::from(
+            "`permalink` should end with either / or .html",
+        ));
+    }
+    Ok(())
+}
+
+/// expand_permalink
+///
+/// Fills in a permalink pattern for one post, giving its URL relative to the top of the site,
+/// like `2023/08/writing-a-blog/` or `writing-a-blog.html`.
+/// The folder is where the post sits within `posts`, and is empty for posts at the top.
+pub fn expand_permalink(pattern: &str, date: &NaiveDate, slug: &str, folder: &str) -> String {
+    let expanded = pattern
+        .replace(":year", &format!("{:04}", date.year()))
+        .replace(":month", &format!("{:02}", date.month()))
+        .replace(":day", &format!("{:02}", date.day()))
+        .replace(":folder", folder)
+        .replace(":slug", slug);
+
+    // An empty folder would otherwise leave a doubled slash behind. Dots are resolved as well,
+    // and `..` never climbs above the top of the site, so the page is always written inside it.
+    normalise_path(&expanded)
+}
+
+/// permalink_file_name
+///
+/// The file a URL is written to, relative to the output directory.
+/// A URL ending in `/` is a folder, served by the `index.html` inside it, and an empty one is the top of the site.
+pub fn permalink_file_name(url: &str) -> String {
+    if url.is_empty() || url.ends_with('/') {
+        format!("{}index.html", url)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 8, 1).unwrap()
+    }
+
+    #[test]
+    fn valid_permalinks_are_accepted() {
+        assert_eq!(validate_permalink(DEFAULT_PERMALINK), Ok(()));
+        assert_eq!(validate_permalink("/:year/:month/:day/:slug/"), Ok(()));
+        assert_eq!(validate_permalink("/blog/:slug.html"), Ok(()));
+    }
+
+    #[test]
+    fn invalid_permalinks_are_rejected() {
+        assert!(validate_permalink("/:year/post.html").is_err());
+        assert!(validate_permalink("/:slug/:week/").is_err());
+        assert!(validate_permalink("/:slug").is_err());
+        assert!(validate_permalink("/../:slug/").is_err());
+        assert!(validate_permalink("/./:slug.html").is_err());
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(
+            expand_permalink("/:year/:month/:day/:slug/", &date(), "post", ""),
+            "2023/08/01/post/"
+        );
+        assert_eq!(
+            expand_permalink(DEFAULT_PERMALINK, &date(), "intro", "rust/basics"),
+            "rust/basics/intro.html"
+        );
+    }
+
+    #[test]
+    fn an_empty_folder_leaves_no_doubled_slash() {
+        assert_eq!(
+            expand_permalink(DEFAULT_PERMALINK, &date(), "post", ""),
+            "post.html"
+        );
+    }
+
+    #[test]
+    fn expanded_urls_stay_inside_the_site() {
+        assert_eq!(expand_permalink("/:slug/", &date(), "..", ""), "");
+        assert_eq!(
+            expand_permalink("/:folder/:slug.html", &date(), "x", "../.."),
+            "x.html"
+        );
+        assert_eq!(expand_permalink("/:slug/", &date(), ".", ""), "");
+    }
+
+    #[test]
+    fn folder_urls_are_written_to_their_index() {
+        assert_eq!(permalink_file_name("2023/post/"), "2023/post/index.html");
+        assert_eq!(permalink_file_name("post.html"), "post.html");
+        assert_eq!(permalink_file_name(""), "index.html");
+    }
+}
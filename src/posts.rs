@@ -1,27 +1,191 @@
-use crate::metadata::MetaData;
+use std::cmp::Reverse;
+
+use crate::{category::get_category_path, metadata::MetaData};
+
+/// A comfortable reading pace, used to estimate reading times.
+const WORDS_PER_MINUTE: usize = 200;
 
 #[derive(Debug, Clone)]
 pub struct Post {
     pub metadata: MetaData,
     pub content: String,
+    /// The rendered post on its own, without the title, date, header or footer of its page.
+    pub body: String,
+    /// Roughly how many minutes the post takes to read.
+    pub reading_time: usize,
     pub path: String,
     pub public_link: String,
+    /// Where the rendered page is written, relative to the output directory.
+    pub file_name: String,
+    /// Where the post was read from, relative to the input directory, like `posts/rust/intro.md`.
+    pub source: String,
+    /// Whether the post goes in the feeds, which only the posts of a section with `feeds: false` don't.
+    pub in_feeds: bool,
+    /// The page from the previous build is still up to date, so it doesn't need writing again.
+    pub from_cache: bool,
+}
+
+/// Everything in a post before this marker is its excerpt. The marker itself isn't shown.
+pub const MORE_MARKER: &str = "<!-- more -->";
+
+/// create_excerpt
+///
+/// A plain text summary for a post whose front matter doesn't have one.
+/// Uses everything before the `<!-- more -->` marker when the post has one, otherwise the first `max_words` words.
+pub fn create_excerpt(
+    markdown_before_more: Option<&str>,
+    body_html: &str,
+    max_words: usize,
+) -> String {
+    if let Some(markdown) = markdown_before_more {
+        return strip_html(&markdown::to_html(markdown));
+    }
+
+    let text = strip_html(body_html);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() <= max_words {
+        text
+    } else {
+        format!("{}…", words[..max_words].join(" "))
+    }
+}
+
+/// reading_time
+///
+/// Estimates how many minutes a rendered post takes to read, never less than one.
+pub fn reading_time(body_html: &str) -> usize {
+    let words = strip_html(body_html).split_whitespace().count();
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
+/// strip_html
+///
+/// Drops the tags from a block of HTML and decodes the entities markdown escapes, leaving the text on a single line.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            // Tags usually separate words, so leave a gap where one was
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// create_post_preview_html
+///
+/// Shows a post on a listing page with its cover image, date, reading time, categories and summary,
+/// rather than only its title.
+pub fn create_post_preview_html(post: &Post) -> String {
+    let mut preview_html = String::from("<div class=\"post-preview\">\n");
+    if let Some(cover) = &post.metadata.cover {
+        preview_html.push_str(&format!(
+            "<a href=\"{}\"><img class=\"post-preview-cover\" src=\"{}\" alt=\"\"></a>\n",
+            post.path, cover
+        ));
+    }
+    preview_html.push_str(&format!(
+        "<h3><a href=\"{}\">{}</a></h3>\n",
+        post.path, post.metadata.title
+    ));
+
+    let categories: Vec<String> = post
+        .metadata
+        .categories
+        .iter()
+        .map(|category| {
+            format!(
+                "<a href=\"./{}\">{}</a>",
+                get_category_path(category),
+                category
+            )
+        })
+        .collect();
+    let mut details = format!(
+        "{} · {} min read",
+        post.metadata.date.format("%-d %B %Y"),
+        post.reading_time
+    );
+    if !categories.is_empty() {
+        details.push_str(&format!(" · {}", categories.join(", ")));
+    }
+    preview_html.push_str(&format!(
+        "<p class=\"post-preview-details\">{}</p>\n",
+        details
+    ));
+
+    if !post.metadata.summary.is_empty() {
+        preview_html.push_str(&format!(
+            "<p class=\"post-preview-summary\">{}</p>\n",
+            escape_html(&post.metadata.summary)
+        ));
+    }
+    preview_html.push_str("</div>");
+    preview_html
+}
+
+/// Summaries are plain text, so anything that looks like markup has to be escaped before it goes on a page.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
-pub fn create_recent_posts_html(posts: &Vec<Post>, num_posts: usize) -> String {
+pub fn create_recent_posts_html(posts: &[Post], num_posts: usize, with_previews: bool) -> String {
     let mut recent_posts_html =
         String::from("<div id=\"recent-posts\">\n<h2>Recent Posts</h2>\n<ul>");
 
-    let mut sorted_posts = posts.clone();
-    sorted_posts.sort_by(|a, b| b.metadata.date.cmp(&a.metadata.date));
+    let mut sorted_posts = posts.to_vec();
+    sorted_posts.sort_by_key(|post| Reverse(post.metadata.date));
     for post in sorted_posts.iter().take(num_posts) {
-        recent_posts_html.push_str(&format!(
-            "<li><a href=\"{}\">{}</a></li>\n",
-            post.path, post.metadata.title
-        ));
+        if with_previews {
+            recent_posts_html.push_str(&format!("<li>{}</li>\n", create_post_preview_html(post)));
+        } else {
+            recent_posts_html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                post.path, post.metadata.title
+            ));
+        }
     }
 
     recent_posts_html.push_str("<a href=\"./all.html\">» all posts</a>");
     recent_posts_html.push_str("</ul>\n</div>\n");
     recent_posts_html
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excerpts_use_everything_before_the_more_marker() {
+        let body = markdown::to_html("Intro with *emphasis* &amp; more.\n\nThe rest.");
+        assert_eq!(
+            create_excerpt(Some("Intro with *emphasis* &amp; more.\n\n"), &body, 2),
+            "Intro with emphasis & more."
+        );
+    }
+
+    #[test]
+    fn excerpts_are_cut_to_the_first_words_without_a_marker() {
+        let body = markdown::to_html("# Title\n\nOne two three four.");
+        assert_eq!(create_excerpt(None, &body, 3), "Title One two…");
+        assert_eq!(create_excerpt(None, &body, 5), "Title One two three four.");
+    }
+}
@@ -0,0 +1,183 @@
+use std::{fs, path::Path};
+
+use crate::{
+    config::{RedirectRules, SiteConfig},
+    error::BuildError,
+    files::write_to_file,
+    posts::Post,
+    urls::{normalise_path, resolve_url},
+};
+
+/// Redirect
+///
+/// One old path of a post, from its `aliases`, and where it now lives.
+struct Redirect<'a> {
+    /// The old URL within the site, like `old-post.html` or `2019/old-post/`.
+    from: String,
+    /// The file the redirect page is written to, relative to the output directory.
+    file_name: String,
+    post: &'a Post,
+}
+
+/// build_redirects
+///
+/// Writes a page for every alias of every post that sends readers, and search engines, on to where the post is now.
+/// Also writes the redirect rule files the config asks for, so hosts that support them can redirect without the page.
+pub fn build_redirects(
+    output_dir: &Path,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
+    let redirects: Vec<Redirect> = posts
+        .iter()
+        .flat_map(|post| {
+            post.metadata.aliases.iter().map(move |alias| Redirect {
+                from: alias_url(alias),
+                file_name: alias_file_name(alias),
+                post,
+            })
+        })
+        .collect();
+
+    for redirect in &redirects {
+        let levels_down = redirect.file_name.matches('/').count();
+        let target = resolve_url(post_url(redirect.post), levels_down, config.links, config);
+        let page = create_redirect_html(&target, &redirect.post.public_link);
+        let page_path = output_dir.join(&redirect.file_name);
+        if let Some(parent) = page_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| BuildError::io(parent, e))?;
+        }
+        write_to_file(output_dir, &redirect.file_name, &page)?;
+    }
+    if !redirects.is_empty() {
+        println!("Wrote {} redirect pages", redirects.len());
+    }
+
+    for rules in &config.redirect_rules {
+        let contents = match rules {
+            RedirectRules::Netlify => create_netlify_rules(&redirects, config),
+            RedirectRules::Nginx => create_nginx_rules(&redirects, config),
+        };
+        write_to_file(output_dir, rules.file_name(), &contents)?;
+    }
+    Ok(())
+}
+
+/// alias_url
+///
+/// An alias as a URL within the site, without the leading `/` or `./` it may be written with,
+/// and never climbing above the top of the site.
+fn alias_url(alias: &str) -> String {
+    normalise_path(alias)
+}
+
+/// alias_file_name
+///
+/// The file the redirect page for an alias is written to. Anything that isn't an `.html` page is treated
+/// as a folder, served by the `index.html` inside it.
+pub fn alias_file_name(alias: &str) -> String {
+    let url = alias_url(alias);
+    if url.ends_with(".html") {
+        url
+    } else if url.is_empty() {
+        String::from("index.html")
+    } else {
+        format!("{}/index.html", url.trim_end_matches('/'))
+    }
+}
+
+/// post_url
+///
+/// The URL of a post within the site, like `2023/08/writing-a-blog/`.
+fn post_url(post: &Post) -> &str {
+    post.path.strip_prefix("./").unwrap_or(&post.path)
+}
+
+/// create_redirect_html
+///
+/// A page that sends the browser straight on to `target`, names `canonical` as the real address of the content,
+/// and shows a link for anything that doesn't follow the refresh.
+fn create_redirect_html(target: &str, canonical: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"UTF-8\">
+<title>Redirecting…</title>
+<link rel=\"canonical\" href=\"{canonical}\">
+<meta http-equiv=\"refresh\" content=\"0; url={target}\">
+<meta name=\"robots\" content=\"noindex\">
+</head>
+<body>
+<p>This post has moved to <a href=\"{target}\">{canonical}</a>.</p>
+</body>
+</html>
+"
+    )
+}
+
+/// create_netlify_rules
+///
+/// A `_redirects` file, as read by Netlify and Cloudflare Pages, with a permanent redirect for every alias.
+fn create_netlify_rules(redirects: &[Redirect], config: &SiteConfig) -> String {
+    redirects
+        .iter()
+        .map(|redirect| {
+            format!(
+                "{}/{} {}/{} 301\n",
+                config.base_path,
+                redirect.from,
+                config.base_path,
+                post_url(redirect.post)
+            )
+        })
+        .collect()
+}
+
+/// create_nginx_rules
+///
+/// A `redirects.conf` to `include` in an nginx `server` block, with a permanent redirect for every alias.
+fn create_nginx_rules(redirects: &[Redirect], config: &SiteConfig) -> String {
+    redirects
+        .iter()
+        .map(|redirect| {
+            format!(
+                "location = {}/{} {{ return 301 {}/{}; }}\n",
+                config.base_path,
+                redirect.from,
+                config.base_path,
+                post_url(redirect.post)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_of_pages_are_written_as_they_are() {
+        assert_eq!(alias_file_name("old-post.html"), "old-post.html");
+        assert_eq!(alias_file_name("/2019/old-post.html"), "2019/old-post.html");
+    }
+
+    #[test]
+    fn other_aliases_are_written_as_folders() {
+        assert_eq!(
+            alias_file_name("2019/old-post/"),
+            "2019/old-post/index.html"
+        );
+        assert_eq!(
+            alias_file_name("/2019/old-post"),
+            "2019/old-post/index.html"
+        );
+        assert_eq!(alias_file_name("/"), "index.html");
+    }
+
+    #[test]
+    fn aliases_cannot_point_outside_the_site() {
+        assert_eq!(alias_file_name("../../etc/old.html"), "etc/old.html");
+        assert_eq!(alias_file_name("a/./b/../old/"), "a/old/index.html");
+    }
+}
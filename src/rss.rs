@@ -3,18 +3,65 @@ extern crate rss;
 use rss::{ChannelBuilder, Guid, ItemBuilder};
 use std::path::Path;
 
+use crate::category::Category;
 use crate::config::SiteConfig;
+use crate::error::BuildError;
+use crate::feed::{feed_content, feed_posts};
 use crate::files::write_to_file;
 use crate::posts::Post;
 
-pub fn build_rss_feed(output_dir: &Path, posts: Vec<Post>, config: &SiteConfig) {
+pub fn build_rss_feed(
+    output_dir: &Path,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
+    write_rss_feed(
+        output_dir,
+        "feed.xml",
+        &config.title,
+        &config.site_url(),
+        &config.description,
+        posts,
+        config,
+    )
+}
+
+/// build_category_rss_feed
+///
+/// Writes a feed holding only the posts of one category, so readers can follow just the topics they care about.
+pub fn build_category_rss_feed(
+    output_dir: &Path,
+    category: &Category,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
+    write_rss_feed(
+        output_dir,
+        &category.feed_path(),
+        &format!("{} - {}", config.title, category.name),
+        &config.public_url(&category.path),
+        &format!("Posts about {} from {}", category.name, config.title),
+        posts,
+        config,
+    )
+}
+
+fn write_rss_feed(
+    output_dir: &Path,
+    file_name: &str,
+    title: &str,
+    link: &str,
+    description: &str,
+    posts: &[Post],
+    config: &SiteConfig,
+) -> Result<(), BuildError> {
     let mut items = Vec::new();
-    for post in posts {
+    for post in feed_posts(posts, config) {
         let item = ItemBuilder::default()
             .title(Some(post.metadata.title.clone()))
             .link(Some(post.public_link.clone()))
             .description(Some(post.metadata.summary.clone()))
-            .content(Some(post.content))
+            .content(feed_content(post, config))
             .pub_date(Some(post.metadata.rss_formatted_date()))
             .guid(Some(Guid {
                 value: post.public_link.clone(),
@@ -25,13 +72,13 @@ pub fn build_rss_feed(output_dir: &Path, posts: Vec<Post>, config: &SiteConfig)
     }
 
     let channel = ChannelBuilder::default()
-        .title(&config.title)
-        .link(&config.url)
-        .description(&config.description)
+        .title(title)
+        .link(link)
+        .description(description)
         .items(items)
         .build();
 
     let output = channel.to_string();
 
-    write_to_file(output_dir, "feed.xml", &output).unwrap();
+    write_to_file(output_dir, file_name, &output)
 }
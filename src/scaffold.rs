@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+use chrono::Local;
+
+use crate::{
+    error::BuildError,
+    files::{find_markdown_files, read_file, write_to_file},
+    metadata::MetaData,
+    slug::slugify,
+};
+
+/// new_post
+///
+/// Creates `posts/<slug>.md` in the input directory with a front matter block holding the title,
+/// today's date, the categories and a summary. Anything not passed on the command line is asked for,
+/// offering the categories already used by existing posts. An existing post is never overwritten.
+pub fn new_post(
+    input_dir: &Path,
+    title: Option<String>,
+    categories: Vec<String>,
+    summary: Option<String>,
+) -> Result<(), BuildError> {
+    let posts_dir = input_dir.join("posts");
+    let existing_categories = find_existing_categories(&posts_dir)?;
+    let interactive = io::stdin().is_terminal();
+
+    let title = match title {
+        Some(title) => title,
+        None if interactive => prompt("Title: ")?,
+        None => String::new(),
+    };
+    if title.trim().is_empty() {
+        return Err(BuildError::Message(String::from("A post needs a title")));
+    }
+
+    let categories = if categories.is_empty() && interactive {
+        prompt_for_categories(&existing_categories)?
+    } else {
+        categories
+            .iter()
+            .map(|category| match_existing_category(category, &existing_categories))
+            .collect()
+    };
+
+    let summary = match summary {
+        Some(summary) => summary,
+        None if interactive => prompt("Summary (optional): ")?,
+        None => String::new(),
+    };
+
+    // Named with the same slug the build gives the post, or `untitled` for a title without any letters or numbers
+    let slug = match slugify(&title) {
+        slug if slug.is_empty() => String::from("untitled"),
+        slug => slug,
+    };
+    let file_name = format!("{}.md", slug);
+    let post_path = posts_dir.join(&file_name);
+    if post_path.exists() {
+        return Err(BuildError::Message(format!(
+            "{} already exists",
+            post_path.display()
+        )));
+    }
+
+    let contents = create_front_matter(title.trim(), &categories, summary.trim());
+
+    // Make sure the build will read back exactly what we meant to write
+    let read_back = MetaData::read_metadata_and_contents(&post_path, &contents, &[]);
+    if !matches!(read_back, Ok((metadata, _)) if metadata.title == title.trim() && metadata.categories == categories)
+    {
+        return Err(BuildError::Message(String::from(
+            "Generated front matter did not read back correctly",
+        )));
+    }
+
+    fs::create_dir_all(&posts_dir).map_err(|e| BuildError::io(&posts_dir, e))?;
+    write_to_file(&posts_dir, &file_name, &contents)?;
+    println!("Created {}", post_path.display());
+    Ok(())
+}
+
+/// create_front_matter
+///
+/// Builds the contents of a new post, in the format `MetaData::read_metadata_and_contents` expects.
+fn create_front_matter(title: &str, categories: &[String], summary: &str) -> String {
+    let today = Local::now().date_naive();
+
+    let mut front_matter = String::from("---\n");
+    front_matter.push_str(&format!("title: {}\n", quote_yaml_string(title)));
+    front_matter.push_str(&format!("date: {}\n", today.format("%Y-%m-%d")));
+    if categories.is_empty() {
+        front_matter.push_str("categories: []\n");
+    } else {
+        front_matter.push_str("categories:\n");
+        for category in categories {
+            front_matter.push_str(&format!("  - {}\n", quote_yaml_string(category)));
+        }
+    }
+    front_matter.push_str(&format!("summary: {}\n", quote_yaml_string(summary)));
+    front_matter.push_str("---\n\n");
+    front_matter
+}
+
+/// find_existing_categories
+///
+/// Reads the front matter of every post, including those in sub folders,
+/// and returns the categories in use, sorted and without duplicates.
+/// Posts with broken front matter are skipped here, `check` will point them out.
+fn find_existing_categories(posts_dir: &Path) -> Result<Vec<String>, BuildError> {
+    let mut categories: Vec<String> = Vec::new();
+    if posts_dir.is_dir() {
+        for path in find_markdown_files(posts_dir)? {
+            let Ok(file_contents) = read_file(&path) else {
+                continue;
+            };
+            if let Ok((metadata, _)) =
+                MetaData::read_metadata_and_contents(&path, &file_contents, &[])
+            {
+                for category in metadata.categories {
+                    if !categories.contains(&category) {
+                        categories.push(category);
+                    }
+                }
+            }
+        }
+    }
+    categories.sort();
+    Ok(categories)
+}
+
+/// prompt_for_categories
+///
+/// Lists the existing categories and reads a comma separated answer,
+/// where each entry is either the number of an existing category or the name of a new one.
+fn prompt_for_categories(existing_categories: &[String]) -> Result<Vec<String>, BuildError> {
+    if !existing_categories.is_empty() {
+        println!("Existing categories:");
+        for (i, category) in existing_categories.iter().enumerate() {
+            println!("  {}) {}", i + 1, category);
+        }
+    }
+    let answer = prompt("Categories (numbers or new names, comma separated): ")?;
+
+    let mut categories: Vec<String> = Vec::new();
+    for entry in answer.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let category = match entry.parse::<usize>() {
+            Ok(number) if number >= 1 && number <= existing_categories.len() => {
+                existing_categories[number - 1].clone()
+            }
+            _ => match_existing_category(entry, existing_categories),
+        };
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    Ok(categories)
+}
+
+/// match_existing_category
+///
+/// Reuses the spelling of an existing category when the name only differs by case,
+/// so that "blog writing" doesn't end up as a second "Blog Writing" category.
+fn match_existing_category(category: &str, existing_categories: &[String]) -> String {
+    let category = category.trim();
+    existing_categories
+        .iter()
+        .find(|existing| existing.eq_ignore_ascii_case(category))
+        .cloned()
+        .unwrap_or_else(|| category.to_string())
+}
+
+fn prompt(question: &str) -> Result<String, BuildError> {
+    print!("{}", question);
+    let mut answer = String::new();
+    io::stdout()
+        .flush()
+        .and_then(|_| io::stdin().lock().read_line(&mut answer))
+        .map_err(|e| BuildError::Message(format!("Unable to read answer: {}", e)))?;
+    Ok(answer.trim().to_string())
+}
+
+fn quote_yaml_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
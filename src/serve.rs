@@ -0,0 +1,323 @@
+use std::{
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use notify::{recommended_watcher, Event, EventKind, RecursiveMode, Watcher};
+use tiny_http::{Header, Response, Server};
+
+use crate::{
+    argparse::{ConfigArgs, PublishArgs},
+    build_site,
+    config::SiteConfig,
+    error::{report_errors, BuildError},
+};
+
+/// How long to wait for the file system to settle before rebuilding, so that a burst of
+/// saves only triggers a single rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// serve
+///
+/// Builds the site once, serves the output directory on localhost and rebuilds whenever
+/// anything under the input directory changes. Runs until the process is stopped.
+pub fn serve(
+    input_dir: &Path,
+    output_dir: &Path,
+    port: u16,
+    publish: PublishArgs,
+    config_args: &ConfigArgs,
+) -> Result<(), BuildError> {
+    if let Err(errors) = build_site(input_dir, output_dir, publish, config_args) {
+        report_errors(&errors);
+    }
+
+    // Served under the same base path as the real site, so root relative links work too
+    let base_path = SiteConfig::read_site_config(input_dir, config_args)
+        .map(|config| config.base_path)
+        .unwrap_or_default();
+
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| BuildError::Message(format!("Unable to serve on port {}: {}", port, e)))?;
+    println!(
+        "Serving {} at http://localhost:{}{}/",
+        output_dir.display(),
+        port,
+        base_path
+    );
+
+    let root = output_dir.to_path_buf();
+    thread::spawn(move || serve_requests(server, &root, &base_path));
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    // The watcher stops watching once it is dropped, so it is kept around until we exit
+    let _watcher = recommended_watcher(tx)
+        .and_then(|mut watcher| {
+            watcher.watch(input_dir, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        })
+        .map_err(|e| {
+            BuildError::Message(format!("Unable to watch {}: {}", input_dir.display(), e))
+        })?;
+    println!("Watching {} for changes...", input_dir.display());
+
+    // The output folder may live inside the input folder, in which case our own writes
+    // must not trigger another rebuild.
+    let ignored_dir = fs::canonicalize(output_dir).unwrap_or_else(|_| output_dir.to_path_buf());
+
+    for event in &rx {
+        if !is_relevant_change(&event, &ignored_dir) {
+            continue;
+        }
+
+        // Swallow the rest of the burst before rebuilding.
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        println!("Change detected. Rebuilding...");
+        if let Err(errors) = build_site(input_dir, output_dir, publish, config_args) {
+            report_errors(&errors);
+        }
+    }
+    Ok(())
+}
+
+fn is_relevant_change(event: &notify::Result<Event>, ignored_dir: &Path) -> bool {
+    match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| !path.starts_with(ignored_dir))
+        }
+        Err(e) => {
+            println!("Error watching files: {}", e);
+            false
+        }
+    }
+}
+
+fn serve_requests(server: Server, root: &Path, base_path: &str) {
+    for request in server.incoming_requests() {
+        let result = match resolve_request_path(root, base_path, request.url()) {
+            Some(RequestTarget::File(path)) => match File::open(&path) {
+                Ok(file) => {
+                    let content_type =
+                        Header::from_bytes(&b"Content-Type"[..], content_type_for(&path)).unwrap();
+                    request.respond(Response::from_file(file).with_header(content_type))
+                }
+                Err(_) => request.respond(not_found()),
+            },
+            Some(RequestTarget::Redirect(location)) => {
+                let location = Header::from_bytes(&b"Location"[..], location.as_bytes()).unwrap();
+                request.respond(Response::empty(301).with_header(location))
+            }
+            None => request.respond(not_found()),
+        };
+
+        if let Err(e) = result {
+            println!("Error responding to request: {}", e);
+        }
+    }
+}
+
+fn not_found() -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string("404 Not Found").with_status_code(404)
+}
+
+/// What a request is answered with.
+#[derive(Debug, PartialEq)]
+enum RequestTarget {
+    File(PathBuf),
+    /// A folder asked for without a trailing slash is sent to the URL with one, like a static host
+    /// would, so relative links on its index page are resolved from inside it.
+    Redirect(String),
+}
+
+/// resolve_request_path
+///
+/// Maps a request URL onto a file inside the output directory, the way a static host would.
+/// Only URLs under the site's base path are served.
+/// Directories resolve to their index.html, and anything trying to escape the root is refused.
+fn resolve_request_path(root: &Path, base_path: &str, url: &str) -> Option<RequestTarget> {
+    let url_path = url.split(['?', '#']).next().unwrap_or("");
+    let decoded = percent_decode(url_path);
+    let decoded = decoded.strip_prefix(base_path)?;
+    if !decoded.is_empty() && !decoded.starts_with('/') {
+        return None;
+    }
+
+    let mut path = root.to_path_buf();
+    for component in Path::new(decoded.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if path.is_dir() {
+        if !url_path.ends_with('/') {
+            return Some(RequestTarget::Redirect(format!(
+                "{}/{}",
+                url_path,
+                &url[url_path.len()..]
+            )));
+        }
+        path.push("index.html");
+    }
+    if path.is_file() {
+        Some(RequestTarget::File(path))
+    } else {
+        None
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn content_type_for(path: &Path) -> &'static [u8] {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => b"text/html; charset=utf-8",
+        "css" => b"text/css; charset=utf-8",
+        "xml" => b"application/xml; charset=utf-8",
+        "json" => b"application/json; charset=utf-8",
+        "txt" => b"text/plain; charset=utf-8",
+        "jpg" | "jpeg" => b"image/jpeg",
+        "png" => b"image/png",
+        "gif" => b"image/gif",
+        "svg" => b"image/svg+xml",
+        "webp" => b"image/webp",
+        "ico" => b"image/x-icon",
+        _ => b"application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// An output directory with a home page and one post in a folder, removed once the test is done.
+    fn output_dir() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("rust")).unwrap();
+        fs::write(dir.path().join("index.html"), "home").unwrap();
+        fs::write(dir.path().join("rust").join("intro.html"), "intro").unwrap();
+        fs::write(dir.path().join("rust").join("index.html"), "rust").unwrap();
+        dir
+    }
+
+    fn file(path: PathBuf) -> Option<RequestTarget> {
+        Some(RequestTarget::File(path))
+    }
+
+    fn redirect(location: &str) -> Option<RequestTarget> {
+        Some(RequestTarget::Redirect(location.to_string()))
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("my%20post.html"), "my post.html");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+        assert_eq!(percent_decode("%2e%2e/secret"), "../secret");
+    }
+
+    #[test]
+    fn broken_percent_escapes_are_kept() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        assert_eq!(percent_decode("a%4"), "a%4");
+    }
+
+    #[test]
+    fn requests_resolve_to_files_and_folder_indexes() {
+        let dir = output_dir();
+        let root = dir.path();
+        assert_eq!(
+            resolve_request_path(root, "", "/"),
+            file(root.join("index.html"))
+        );
+        assert_eq!(
+            resolve_request_path(root, "", "/rust/intro.html?x=1#top"),
+            file(root.join("rust").join("intro.html"))
+        );
+        assert_eq!(
+            resolve_request_path(root, "", "/rust/"),
+            file(root.join("rust").join("index.html"))
+        );
+        assert_eq!(resolve_request_path(root, "", "/missing.html"), None);
+    }
+
+    #[test]
+    fn folders_without_a_trailing_slash_are_redirected() {
+        let dir = output_dir();
+        let root = dir.path();
+        assert_eq!(resolve_request_path(root, "", "/rust"), redirect("/rust/"));
+        assert_eq!(
+            resolve_request_path(root, "", "/rust?x=1"),
+            redirect("/rust/?x=1")
+        );
+        assert_eq!(
+            resolve_request_path(root, "/notes", "/notes"),
+            redirect("/notes/")
+        );
+    }
+
+    #[test]
+    fn requests_are_served_from_under_the_base_path() {
+        let dir = output_dir();
+        let root = dir.path();
+        assert_eq!(
+            resolve_request_path(root, "/notes", "/notes/rust/intro.html"),
+            file(root.join("rust").join("intro.html"))
+        );
+        assert_eq!(
+            resolve_request_path(root, "/notes", "/notes/"),
+            file(root.join("index.html"))
+        );
+        assert_eq!(resolve_request_path(root, "/notes", "/index.html"), None);
+        assert_eq!(
+            resolve_request_path(root, "/notes", "/notesx/index.html"),
+            None
+        );
+    }
+
+    #[test]
+    fn requests_cannot_escape_the_output_directory() {
+        let dir = output_dir();
+        let root = dir.path();
+        assert_eq!(resolve_request_path(root, "", "/../index.html"), None);
+        assert_eq!(
+            resolve_request_path(root, "", "/rust/%2e%2e/%2e%2e/x"),
+            None
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use deunicode::deunicode;
+
+use crate::{error::BuildError, urls::normalise_path};
+
+/// Punctuation that tells names like C++ and C# apart, so it is spelled out rather than dropped.
+const SPELLED_OUT: [(char, &str); 4] = [('+', "plus"), ('#', "sharp"), ('&', "and"), ('@', "at")];
+
+/// slugify
+///
+/// Turns a name into something safe for a file name and URL: transliterated to ASCII and lower case,
+/// with each run of spaces and other punctuation made into a single hyphen.
+/// `C++` becomes `c-plus-plus`, `C#` becomes `c-sharp` and `Café Notes` becomes `cafe-notes`.
+/// Returns an empty string for a name without any letters or numbers.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    let mut needs_hyphen = false;
+    for c in deunicode(name).chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            if needs_hyphen && !slug.is_empty() {
+                slug.push('-');
+            }
+            needs_hyphen = false;
+            slug.push(c.to_ascii_lowercase());
+        } else if let Some((_, word)) = SPELLED_OUT.iter().find(|(symbol, _)| *symbol == c) {
+            if !slug.is_empty() {
+                slug.push('-');
+            }
+            slug.push_str(word);
+            needs_hyphen = true;
+        } else {
+            needs_hyphen = true;
+        }
+    }
+    slug
+}
+
+/// is_safe_slug
+///
+/// Whether a slug given in front matter can be used as it is in a URL and a file name: made only of
+/// letters, numbers, `-`, `_`, `.` and `~`, and not `.` or `..`, which would point at another folder.
+pub fn is_safe_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug != "."
+        && slug != ".."
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'))
+}
+
+/// OutputFiles
+///
+/// Every file a build writes along with what it is written for, so two things that end up with the same
+/// slug fail the build rather than one quietly replacing the other.
+#[derive(Default)]
+pub struct OutputFiles {
+    sources: HashMap<String, String>,
+    files: Vec<String>,
+    errors: Vec<BuildError>,
+}
+
+impl OutputFiles {
+    /// claim
+    ///
+    /// Records that `source` is written to `file_name`, reporting both when something else already is.
+    /// Returns whether the file was free.
+    pub fn claim(&mut self, file_name: &str, source: String) -> bool {
+        let file_name = normalise_path(file_name);
+        // Compared without case, as `Rust.html` and `rust.html` are the same file on some file systems
+        let key = file_name.to_lowercase();
+        match self.sources.get(&key) {
+            Some(existing) => self.errors.push(BuildError::Message(format!(
+                "{} and {} would both be written to {}",
+                existing, source, file_name
+            ))),
+            None => {
+                self.sources.insert(key, source);
+                self.files.push(file_name);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// report
+    ///
+    /// Records a problem with a file name that isn't a clash, like a name with nothing to make a slug from.
+    pub fn report(&mut self, message: String) {
+        self.errors.push(BuildError::Message(message));
+    }
+
+    /// finish
+    ///
+    /// Every file claimed, when each has a single source, otherwise every clash and problem found.
+    pub fn finish(self) -> Result<Vec<String>, Vec<BuildError>> {
+        if self.errors.is_empty() {
+            Ok(self.files)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_made_into_lower_case_words_joined_by_hyphens() {
+        assert_eq!(slugify("Blog Writing"), "blog-writing");
+        assert_eq!(slugify("  Hello,   World!  "), "hello-world");
+        assert_eq!(slugify("post-1"), "post-1");
+        assert_eq!(slugify("snake_case"), "snake_case");
+    }
+
+    #[test]
+    fn punctuation_that_tells_names_apart_is_spelled_out() {
+        assert_eq!(slugify("C++"), "c-plus-plus");
+        assert_eq!(slugify("C#"), "c-sharp");
+        assert_eq!(slugify("R&D"), "r-and-d");
+        assert_eq!(slugify("C++ Tips"), "c-plus-plus-tips");
+    }
+
+    #[test]
+    fn other_scripts_are_transliterated() {
+        assert_eq!(slugify("Café Notes"), "cafe-notes");
+        assert_eq!(slugify("Größe"), "grosse");
+    }
+
+    #[test]
+    fn names_without_letters_or_numbers_give_nothing() {
+        assert_eq!(slugify(""), "");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn only_url_safe_slugs_are_accepted() {
+        assert!(is_safe_slug("my-post_1.v2~draft"));
+        assert!(!is_safe_slug(""));
+        assert!(!is_safe_slug("."));
+        assert!(!is_safe_slug(".."));
+        assert!(!is_safe_slug("a/b"));
+        assert!(!is_safe_slug("a b"));
+        assert!(!is_safe_slug("café"));
+    }
+
+    #[test]
+    fn files_claimed_twice_are_reported_with_both_sources() {
+        let mut files = OutputFiles::default();
+        assert!(files.claim("rust.html", String::from("the post posts/rust.md")));
+        assert!(!files.claim("Rust.html", String::from("the category `Rust`")));
+        let errors = files.finish().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "the post posts/rust.md and the category `Rust` would both be written to Rust.html"
+        );
+    }
+
+    #[test]
+    fn claimed_names_are_normalised() {
+        let mut files = OutputFiles::default();
+        files.claim("index.html", String::from("the home page"));
+        assert!(!files.claim("./index.html", String::from("a post")));
+        assert!(!files.claim("x/../index.html", String::from("another post")));
+        assert_eq!(files.finish().unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn every_claimed_file_is_returned() {
+        let mut files = OutputFiles::default();
+        files.claim("index.html", String::from("the home page"));
+        files.claim("./rust/intro.html", String::from("a post"));
+        assert_eq!(
+            files.finish().unwrap(),
+            vec![String::from("index.html"), String::from("rust/intro.html")]
+        );
+    }
+}
@@ -1,83 +1,189 @@
-use std::{io::Error, path::Path};
+use std::{cmp::Reverse, path::Path};
 
 use chrono::{Datelike, NaiveDate};
 
 use crate::{
-    files::{prepend_go_up_folder_to_path, read_file},
-    posts::{create_recent_posts_html, Post},
+    category::CategoryPosts,
+    error::BuildError,
+    files::read_file,
+    posts::{create_post_preview_html, create_recent_posts_html, Post},
 };
 
 /// get_header
 ///
-/// Takes the path to the input directory and the number of levels down from that directory that we are generating for.
-/// The levels down param controls how many "../" we need to prepend to the links within the header.
-pub fn get_header(input_path: &Path, levels_down: i8) -> Result<String, Error> {
+/// Reads header.md from the input directory and renders it.
+/// Links are left as written, from the top of the site, and resolved for each page once it is complete.
+pub fn get_header(input_path: &Path) -> Result<String, BuildError> {
     let header_path = Path::new(input_path).join("header.md");
-    let path_prepend_text = "../".repeat(levels_down as usize);
-
-    match read_file(&header_path) {
-        Ok(file_contents) => {
-            let header_html = markdown::to_html(&file_contents);
-            if levels_down > 0 {
-                let wrapped_header = format!(
-                    "<header>\n{}\n</header>\n",
-                    header_html.replace("./", &path_prepend_text)
-                );
-                return Ok(wrapped_header);
-            }
-            let wrapped_header = format!("<header>\n{}\n</header>\n", header_html);
-            Ok(wrapped_header)
-        }
-        Err(e) => {
-            println!("Error finding header file: {}", e);
-            Err(e)
-        }
-    }
+    let file_contents = read_file(&header_path)?;
+    let header_html = markdown::to_html(&file_contents);
+    Ok(format!("<header>\n{}\n</header>\n", header_html))
 }
 
 /// get_footer
 ///
-/// Takes the path to the input directory and the number of levels down from that directory that we are generating for.
-/// The levels down param controls how many "../" we need to prepend to the links within the footer.
-pub fn get_footer(input_path: &Path, levels_down: i8) -> Result<String, Error> {
+/// Reads footer.md from the input directory and renders it.
+/// Links are left as written, from the top of the site, and resolved for each page once it is complete.
+pub fn get_footer(input_path: &Path) -> Result<String, BuildError> {
     let footer_path = Path::new(input_path).join("footer.md");
-    let path_prepend_text = "../".repeat(levels_down as usize);
-    match read_file(&footer_path) {
-        Ok(file_contents) => {
-            let footer_html = markdown::to_html(&file_contents);
-            if levels_down > 0 {
-                let wrapped_footer = format!(
-                    "<footer>\n{}\n</footer>\n",
-                    footer_html.replace("./", &path_prepend_text)
-                );
-                return Ok(wrapped_footer);
+    let file_contents = read_file(&footer_path)?;
+    let footer_html = markdown::to_html(&file_contents);
+    Ok(format!("<footer>\n{}\n</footer>\n", footer_html))
+}
+
+pub fn get_index_template(input_path: &Path) -> Result<String, BuildError> {
+    let index_path = input_path.join("index.html");
+    read_file(&index_path)
+}
+
+pub fn add_recent_posts(
+    index_template: &str,
+    posts: &[Post],
+    num_posts: usize,
+    with_previews: bool,
+) -> String {
+    let recent_posts_html = create_recent_posts_html(posts, num_posts, with_previews);
+
+    let index_template = format!("{}\n{}", index_template, recent_posts_html);
+    index_template
+}
+
+/// The number of recent posts shown when index.html doesn't say how many.
+const DEFAULT_RECENT_POSTS: usize = 10;
+
+/// fill_index_template
+///
+/// Replaces the placeholders in index.html with the blocks they stand for:
+/// - `<!-- recent_posts count=5 -->` lists the newest posts, 10 unless a count is given.
+/// - `<!-- categories count=3 -->` lists the categories, each with its newest posts when a count is given.
+/// - `<!-- latest_post count=1 -->` shows the newest posts in full, one unless a count is given.
+///
+/// A template without any placeholders gets the recent posts added to the end, as it always has.
+/// Any other comment is left alone.
+pub fn fill_index_template(
+    index_path: &Path,
+    index_template: &str,
+    posts: &[Post],
+    categories: &CategoryPosts,
+    with_previews: bool,
+) -> Result<String, BuildError> {
+    let mut filled = String::with_capacity(index_template.len());
+    let mut found_placeholder = false;
+    let mut rest = index_template;
+
+    while let Some(start) = rest.find("<!--") {
+        let Some(length) = rest[start..].find("-->") else {
+            break;
+        };
+        let end = start + length + "-->".len();
+        let mut words = rest[start + "<!--".len()..start + length].split_whitespace();
+        let name = words.next().unwrap_or_default();
+
+        let placeholder_error = |message: String| {
+            let offset = index_template.len() - rest.len() + start;
+            BuildError::Template {
+                path: index_path.to_path_buf(),
+                line: Some(index_template[..offset].matches('\n').count() + 1),
+                message,
             }
-            let wrapped_footer = format!("<footer>\n{}\n</footer>\n", footer_html);
-            Ok(wrapped_footer)
-        }
-        Err(e) => {
-            println!("Error finding footer file: {}", e);
-            Err(e)
-        }
+        };
+        let mut count = None;
+        let block_html = match name {
+            "recent_posts" | "categories" | "latest_post" => {
+                for option in words {
+                    match option.strip_prefix("count=").map(str::parse::<usize>) {
+                        Some(Ok(number)) => count = Some(number),
+                        _ => {
+                            return Err(placeholder_error(format!(
+                                "unknown option `{}` for `{}`, expected count=<number>",
+                                option, name
+                            )))
+                        }
+                    }
+                }
+                match name {
+                    "recent_posts" => create_recent_posts_html(
+                        posts,
+                        count.unwrap_or(DEFAULT_RECENT_POSTS),
+                        with_previews,
+                    ),
+                    "categories" => create_categories_html(categories, count.unwrap_or(0)),
+                    _ => create_latest_posts_html(posts, count.unwrap_or(1)),
+                }
+            }
+            _ => {
+                filled.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+        };
+
+        found_placeholder = true;
+        filled.push_str(&rest[..start]);
+        filled.push_str(&block_html);
+        rest = &rest[end..];
+    }
+    filled.push_str(rest);
+
+    if found_placeholder {
+        Ok(filled)
+    } else {
+        Ok(add_recent_posts(
+            index_template,
+            posts,
+            DEFAULT_RECENT_POSTS,
+            with_previews,
+        ))
     }
 }
 
-pub fn get_index_template(input_path: &Path) -> Result<String, Error> {
-    let index_path = input_path.to_str().unwrap().to_string() + "/index.html";
-    match read_file(Path::new(index_path.as_str())) {
-        Ok(file_contents) => Ok(file_contents),
-        Err(e) => {
-            println!("Error finding index file: {}", e);
-            Err(e)
+/// create_categories_html
+///
+/// Lists every category with how many posts it has, along with its newest `num_posts` posts.
+fn create_categories_html(categories: &CategoryPosts, num_posts: usize) -> String {
+    let mut categories_html = String::from("<div id=\"categories\">\n<h2>Categories</h2>\n<ul>\n");
+    for (category, posts) in categories {
+        categories_html.push_str(&format!(
+            "<li><a href=\"./{}\">{}</a> ({})",
+            category.path,
+            category.name,
+            posts.len()
+        ));
+        if num_posts > 0 {
+            let mut sorted_posts = posts.to_vec();
+            sorted_posts.sort_by_key(|post| Reverse(post.metadata.date));
+            categories_html.push_str("\n<ul>\n");
+            for post in sorted_posts.iter().take(num_posts) {
+                categories_html.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    post.path, post.metadata.title
+                ));
+            }
+            categories_html.push_str("</ul>\n");
         }
+        categories_html.push_str("</li>\n");
     }
+    categories_html.push_str("</ul>\n</div>\n");
+    categories_html
 }
 
-pub fn add_recent_posts(index_template: &str, posts: &Vec<Post>, num_posts: usize) -> String {
-    let recent_posts_html = create_recent_posts_html(&posts, num_posts);
+/// create_latest_posts_html
+///
+/// Shows the newest `num_posts` posts in full, each with its title linking to the post's own page.
+fn create_latest_posts_html(posts: &[Post], num_posts: usize) -> String {
+    let mut sorted_posts = posts.to_vec();
+    sorted_posts.sort_by_key(|post| Reverse(post.metadata.date));
 
-    let index_template = format!("{}\n{}", index_template, recent_posts_html);
-    index_template
+    let mut latest_posts_html = String::new();
+    for post in sorted_posts.iter().take(num_posts) {
+        let post_html = add_date_to_body(&post.body, &post.metadata.date);
+        let title = format!("<a href=\"{}\">{}</a>", post.path, post.metadata.title);
+        latest_posts_html.push_str(&format!(
+            "<article class=\"latest-post\">\n{}</article>\n",
+            add_title_to_body(&post_html, &title)
+        ));
+    }
+    latest_posts_html
 }
 
 pub fn add_date_to_body(body: &str, date: &NaiveDate) -> String {
@@ -93,10 +199,9 @@ pub fn add_title_to_body(body: &str, title: &str) -> String {
 pub fn wrap_in_header_and_footer(
     input_path: &Path,
     content_block: &str,
-    levels_down: i8,
-) -> Result<String, Error> {
-    let header_block = get_header(input_path, levels_down)?;
-    let footer_block = get_footer(input_path, levels_down)?;
+) -> Result<String, BuildError> {
+    let header_block = get_header(input_path)?;
+    let footer_block = get_footer(input_path)?;
 
     let wrapped_content = format!("{}{}{}", header_block, content_block, footer_block);
     let wrapped_in_container = format!(
@@ -106,27 +211,31 @@ pub fn wrap_in_header_and_footer(
     Ok(wrapped_in_container)
 }
 
-pub fn add_head(content_block: &str, title: &str, look_up: bool) -> Result<String, Error> {
-    let mut style_path = "style/style.css".to_string();
-    if look_up {
-        style_path = prepend_go_up_folder_to_path(&style_path, 1)
-    }
+pub fn add_head(content_block: &str, title: &str, head_links: &str) -> String {
+    format!(
+        "\n<head>\n{}</head>\n{}",
+        head_contents(title, head_links),
+        content_block
+    )
+}
 
-    let html_with_head = format!(
-        "\n<head>\n<title>{}</title>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n<link rel=\"stylesheet\" href=\"{}\">\n</head>\n{}",
+/// head_contents
+///
+/// Everything that goes inside a page's `<head>`: the title, meta tags, stylesheet and any extra links such as feeds.
+pub fn head_contents(title: &str, head_links: &str) -> String {
+    format!(
+        "<title>{}</title>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n<link rel=\"stylesheet\" href=\"style/style.css\">\n{}",
         title,
-        style_path,
-        content_block
-    );
-    Ok(html_with_head)
+        head_links
+    )
 }
 
-pub fn group_by_year_as_html(posts: &Vec<Post>) -> String {
+pub fn group_by_year_as_html(posts: &[Post], with_previews: bool) -> String {
     let mut sorted_posts: Vec<(i32, Vec<Post>)> = Vec::new();
     for post in posts {
         let year = post.metadata.date.year();
         let mut found = false;
-        for (_i, sorted_post) in sorted_posts.iter_mut().enumerate() {
+        for sorted_post in sorted_posts.iter_mut() {
             if sorted_post.0 == year {
                 sorted_post.1.push(post.clone());
                 found = true;
@@ -138,22 +247,26 @@ pub fn group_by_year_as_html(posts: &Vec<Post>) -> String {
         }
     }
     // Sort by most recent year first
-    sorted_posts.sort_by(|a, b| b.0.cmp(&a.0));
+    sorted_posts.sort_by_key(|(year, _)| Reverse(*year));
 
     let mut all_posts_html = String::new();
     for (year, posts) in sorted_posts {
         let mut year_html = format!("<h2>{}</h2>\n<ul>\n", year);
         // Sort by most recent post first
         let mut posts = posts;
-        posts.sort_by(|a, b| b.metadata.date.cmp(&a.metadata.date));
+        posts.sort_by_key(|post| Reverse(post.metadata.date));
 
         for post in posts {
+            if with_previews {
+                year_html.push_str(&format!("<li>{}</li>\n", create_post_preview_html(&post)));
+                continue;
+            }
             year_html.push_str(
                 format!(
                     "<li><a href=\"{}\">{} - [{}]</a></li>\n",
                     post.path,
                     post.metadata.title,
-                    post.metadata.date.format("%-d %B %Y").to_string()
+                    post.metadata.date.format("%-d %B %Y")
                 )
                 .as_str(),
             );
@@ -163,3 +276,91 @@ pub fn group_by_year_as_html(posts: &Vec<Post>) -> String {
     }
     all_posts_html
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::MetaData;
+
+    use super::*;
+
+    /// A post with only a title and a date, which is all the placeholders list.
+    fn post(title: &str, date: &str) -> Post {
+        let front_matter = format!("---\ntitle: {}\ndate: {}\n---\n", title, date);
+        let (metadata, _) =
+            MetaData::read_metadata_and_contents(Path::new("post.md"), &front_matter, &[]).unwrap();
+        Post {
+            metadata,
+            content: String::new(),
+            body: format!("<p>{} body</p>\n", title),
+            reading_time: 1,
+            path: format!("./{}.html", title.to_lowercase()),
+            public_link: String::new(),
+            file_name: format!("{}.html", title.to_lowercase()),
+            source: format!("posts/{}.md", title.to_lowercase()),
+            in_feeds: true,
+            from_cache: false,
+        }
+    }
+
+    fn fill(index_template: &str) -> Result<String, BuildError> {
+        let posts = [post("Old", "2023-01-01"), post("New", "2024-01-01")];
+        fill_index_template(
+            Path::new("index.html"),
+            index_template,
+            &posts,
+            &Vec::new(),
+            false,
+        )
+    }
+
+    #[test]
+    fn placeholders_are_replaced_in_place() {
+        let filled = fill("<h1>Hi</h1>\n<!-- recent_posts -->\n<p>Bye</p>\n").unwrap();
+        assert!(filled.starts_with("<h1>Hi</h1>\n<div id=\"recent-posts\">"));
+        assert!(filled.ends_with("</div>\n\n<p>Bye</p>\n"));
+        assert!(filled.find("./new.html").unwrap() < filled.find("./old.html").unwrap());
+        assert!(!filled.contains("<!--"));
+    }
+
+    #[test]
+    fn counts_limit_how_many_posts_are_shown() {
+        let filled = fill("<!-- recent_posts count=1 -->").unwrap();
+        assert!(filled.contains("./new.html"));
+        assert!(!filled.contains("./old.html"));
+
+        let filled = fill("<!-- latest_post -->").unwrap();
+        assert_eq!(filled.matches("<article class=\"latest-post\">").count(), 1);
+        assert!(filled.contains("<p>New body</p>"));
+
+        let filled = fill("<!-- latest_post count=2 -->").unwrap();
+        assert_eq!(filled.matches("<article class=\"latest-post\">").count(), 2);
+    }
+
+    #[test]
+    fn unknown_options_are_reported_with_their_line() {
+        let error = fill("<h1>Hi</h1>\n\n<!-- recent_posts limit=3 -->").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "index.html:3: unknown option `limit=3` for `recent_posts`, expected count=<number>"
+        );
+        let error = fill("<!-- latest_post count=two -->").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "index.html:1: unknown option `count=two` for `latest_post`, expected count=<number>"
+        );
+    }
+
+    #[test]
+    fn other_comments_are_kept() {
+        let filled = fill("<!-- a note -->\n<!-- recent_posts -->").unwrap();
+        assert!(filled.starts_with("<!-- a note -->\n<div id=\"recent-posts\">"));
+    }
+
+    #[test]
+    fn templates_without_placeholders_get_the_recent_posts_at_the_end() {
+        let filled = fill("<h1>Hi</h1>\n<!-- a note -->").unwrap();
+        assert!(filled.starts_with("<h1>Hi</h1>\n<!-- a note -->\n<div id=\"recent-posts\">"));
+        assert!(filled.contains("./new.html"));
+        assert!(filled.contains("./old.html"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::config::{LinkMode, SiteConfig};
+
+/// The attributes that hold links to other pages, images or files.
+const URL_ATTRIBUTES: [&str; 2] = ["href", "src"];
+
+/// rewrite_urls
+///
+/// Passes the value of every quoted `href` and `src` attribute in a block of HTML to `rewrite`,
+/// replacing the value with whatever it returns. Returning `None` leaves the value as it was.
+pub fn rewrite_urls(html: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some((value_start, value_end)) = find_next_url(rest) {
+        let value = &rest[value_start..value_end];
+        output.push_str(&rest[..value_start]);
+        match rewrite(value) {
+            Some(new_value) => output.push_str(&new_value),
+            None => output.push_str(value),
+        }
+        rest = &rest[value_end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// find_next_url
+///
+/// Finds the start and end of the value of the next `href` or `src` attribute.
+fn find_next_url(html: &str) -> Option<(usize, usize)> {
+    let bytes = html.as_bytes();
+    let mut search_from = 0;
+    while let Some(offset) = html[search_from..].find('=') {
+        let equals = search_from + offset;
+        search_from = equals + 1;
+
+        let is_url_attribute = URL_ATTRIBUTES.iter().any(|attribute| {
+            html[..equals].ends_with(attribute)
+                && html[..equals - attribute.len()].ends_with(|c: char| c.is_whitespace())
+        });
+        let quote = bytes.get(equals + 1).copied();
+        if !is_url_attribute || !matches!(quote, Some(b'"') | Some(b'\'')) {
+            continue;
+        }
+
+        let value_start = equals + 2;
+        let value_length = html[value_start..].find(quote.unwrap() as char)?;
+        return Some((value_start, value_start + value_length));
+    }
+    None
+}
+
+/// is_absolute_url
+///
+/// Whether a link already points somewhere on its own: it has a scheme such as `https:` or `mailto:`,
+/// is protocol relative, or is only a fragment within the same page.
+pub fn is_absolute_url(url: &str) -> bool {
+    if url.starts_with("//") || url.starts_with('#') {
+        return true;
+    }
+    match url.find(':') {
+        Some(colon) => {
+            let scheme = &url[..colon];
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// site_path
+///
+/// The path within the site that a link in a post, header, footer or template points at.
+/// These are written from the top of the site, either relative like `./images/blog.jpg` or root relative
+/// like `/images/blog.jpg`. Returns `None` for links that aren't to the site: absolute URLs, fragments and
+/// links climbing out with `../`.
+fn site_path(url: &str) -> Option<&str> {
+    if url.is_empty() || is_absolute_url(url) || url.starts_with("../") {
+        return None;
+    }
+    let mut path = url.strip_prefix('/').unwrap_or(url);
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    Some(path)
+}
+
+/// normalise_path
+///
+/// Tidies a path within the site into the one form it is compared and written in: no leading `/`,
+/// no empty or `.` segments, and `..` resolved without ever climbing above the top of the site.
+/// A trailing `/`, for a folder, is kept. `./rust/../images//blog.jpg` gives `images/blog.jpg`.
+pub fn normalise_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalised = segments.join("/");
+    if path.ends_with('/') && !normalised.is_empty() {
+        normalised.push('/');
+    }
+    normalised
+}
+
+/// resolve_url
+///
+/// Writes a link to a path within the site for a page `levels_down` folders below the top of the site.
+pub fn resolve_url(
+    path: &str,
+    levels_down: usize,
+    link_mode: LinkMode,
+    config: &SiteConfig,
+) -> String {
+    match link_mode {
+        LinkMode::Relative if levels_down == 0 => format!("./{}", path),
+        LinkMode::Relative => format!("{}{}", "../".repeat(levels_down), path),
+        LinkMode::Root => format!("{}/{}", config.base_path, path),
+        LinkMode::Absolute => config.public_url(path),
+    }
+}
+
+/// resolve_urls
+///
+/// Rewrites every link to the site in a complete page for where that page is written,
+/// `levels_down` folders below the top of the site, in the given link mode.
+/// Everything else, including any text that merely looks like a path, is left alone.
+pub fn resolve_urls(
+    html: &str,
+    levels_down: usize,
+    link_mode: LinkMode,
+    config: &SiteConfig,
+) -> String {
+    rewrite_urls(html, |url| {
+        let path = site_path(url)?;
+        // Relative links on top level pages are already right as written, root relative ones still need the `./`
+        if link_mode == LinkMode::Relative && levels_down == 0 && !url.starts_with('/') {
+            return None;
+        }
+        Some(resolve_url(path, levels_down, link_mode, config))
+    })
+}
+
+/// make_urls_absolute
+///
+/// Makes every link to the site in a block of HTML from the page at `page_url`, like `rust/intro.html`,
+/// absolute so it works away from the site, such as in a feed reader.
+/// Links written from the top of the site are resolved like `resolve_urls` does, and links climbing
+/// out of the page's folder with `../` are resolved against that folder.
+pub fn make_urls_absolute(html: &str, page_url: &str, config: &SiteConfig) -> String {
+    rewrite_urls(html, |url| {
+        let path = match site_path(url) {
+            Some(path) => path.to_string(),
+            None if url.starts_with("../") => join_url(page_url, url),
+            None => return None,
+        };
+        Some(config.public_url(&path))
+    })
+}
+
+/// join_url
+///
+/// Resolves a relative link against the folder of the page at `page_url`, never climbing above the top of the site.
+/// `join_url("rust/intro.html", "../post-1.html")` is `post-1.html`.
+fn join_url(page_url: &str, url: &str) -> String {
+    let folder = match page_url.rfind('/') {
+        Some(end) => &page_url[..end],
+        None => "",
+    };
+    let mut segments: Vec<&str> = folder.split('/').filter(|s| !s.is_empty()).collect();
+    let mut rest = url;
+    loop {
+        if let Some(after) = rest.strip_prefix("../") {
+            segments.pop();
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("./") {
+            rest = after;
+        } else {
+            break;
+        }
+    }
+    segments.push(rest);
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FeedContent;
+
+    fn config(links: LinkMode) -> SiteConfig {
+        SiteConfig {
+            title: String::from("Notes"),
+            url: String::from("https://ex.com/notes"),
+            base_path: String::from("/notes"),
+            description: String::new(),
+            author: String::new(),
+            feeds: Vec::new(),
+            feed_content: FeedContent::Full,
+            feed_items: None,
+            excerpt_words: 50,
+            previews: Vec::new(),
+            links,
+            permalink: String::new(),
+            redirect_rules: Vec::new(),
+            sections: Vec::new(),
+            pages: Vec::new(),
+            taxonomies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn site_path_strips_leading_slashes_and_dots() {
+        assert_eq!(site_path("./images/blog.jpg"), Some("images/blog.jpg"));
+        assert_eq!(site_path("/all.html"), Some("all.html"));
+        assert_eq!(site_path("././all.html"), Some("all.html"));
+        assert_eq!(site_path("all.html"), Some("all.html"));
+    }
+
+    #[test]
+    fn site_path_skips_links_that_are_not_to_the_site() {
+        assert_eq!(site_path(""), None);
+        assert_eq!(site_path("https://example.com/"), None);
+        assert_eq!(site_path("mailto:me@example.com"), None);
+        assert_eq!(site_path("//cdn.example.com/x.js"), None);
+        assert_eq!(site_path("#top"), None);
+        assert_eq!(site_path("../post-1.html"), None);
+    }
+
+    #[test]
+    fn relative_links_on_top_level_pages_are_kept() {
+        let config = config(LinkMode::Relative);
+        let html = r#"<a href="./all.html">All</a>"#;
+        assert_eq!(resolve_urls(html, 0, LinkMode::Relative, &config), html);
+    }
+
+    #[test]
+    fn root_relative_links_on_top_level_pages_are_made_relative() {
+        let config = config(LinkMode::Relative);
+        let html = r#"<a href="/all.html">All</a>"#;
+        assert_eq!(
+            resolve_urls(html, 0, LinkMode::Relative, &config),
+            r#"<a href="./all.html">All</a>"#
+        );
+    }
+
+    #[test]
+    fn links_are_resolved_for_the_depth_of_the_page() {
+        let config = config(LinkMode::Relative);
+        let html = r#"<a href="/all.html"><img src='./images/a.jpg'></a>"#;
+        assert_eq!(
+            resolve_urls(html, 2, LinkMode::Relative, &config),
+            r#"<a href="../../all.html"><img src='../../images/a.jpg'></a>"#
+        );
+    }
+
+    #[test]
+    fn root_and_absolute_links_include_the_base_path() {
+        let config = config(LinkMode::Root);
+        let html = r#"<a href="./all.html">All</a>"#;
+        assert_eq!(
+            resolve_urls(html, 1, LinkMode::Root, &config),
+            r#"<a href="/notes/all.html">All</a>"#
+        );
+        assert_eq!(
+            resolve_urls(html, 1, LinkMode::Absolute, &config),
+            r#"<a href="https://ex.com/notes/all.html">All</a>"#
+        );
+    }
+
+    #[test]
+    fn other_links_and_text_are_left_alone() {
+        let config = config(LinkMode::Relative);
+        let html =
+            r##"<a href="https://example.com/">x</a> <a href="#top">y</a> data="./all.html""##;
+        assert_eq!(resolve_urls(html, 1, LinkMode::Relative, &config), html);
+    }
+
+    #[test]
+    fn feed_links_climbing_out_of_the_post_folder_are_made_absolute() {
+        let config = config(LinkMode::Relative);
+        let html = r#"<a href="../post-1.html">One</a> <img src="./images/a.jpg">"#;
+        assert_eq!(
+            make_urls_absolute(html, "rust/intro.html", &config),
+            r#"<a href="https://ex.com/notes/post-1.html">One</a> <img src="https://ex.com/notes/images/a.jpg">"#
+        );
+    }
+
+    #[test]
+    fn paths_are_normalised() {
+        assert_eq!(
+            normalise_path("./rust/../images//blog.jpg"),
+            "images/blog.jpg"
+        );
+        assert_eq!(normalise_path("/2023/08/post/"), "2023/08/post/");
+        assert_eq!(normalise_path("../../index.html"), "index.html");
+        assert_eq!(normalise_path("./"), "");
+    }
+
+    #[test]
+    fn joined_links_never_climb_above_the_site() {
+        assert_eq!(join_url("rust/intro.html", "../post-1.html"), "post-1.html");
+        assert_eq!(join_url("2023/08/post/", "../other/"), "2023/08/other/");
+        assert_eq!(join_url("intro.html", "../../x.html"), "x.html");
+    }
+}